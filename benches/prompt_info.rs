@@ -0,0 +1,21 @@
+use std::hint;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use python_launcher::cli::Action;
+
+// `--prompt-info` runs on every shell prompt, so it must stay well under the
+// ~10ms it takes for a prompt to feel sluggish; it never executes Python unless
+// probing for pre-releases.
+fn criterion_benchmark(c: &mut Criterion) {
+    let argv = ["py", "--prompt-info"].map(String::from);
+    c.bench_function("Prompt info", |b| {
+        b.iter(|| {
+            let action = Action::from_main(hint::black_box(&argv));
+            hint::black_box(action)
+        });
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -6,21 +6,101 @@
 use std::{
     collections::HashMap,
     env,
+    ffi::{OsStr, OsString},
     fmt::Write,
-    fs::File,
+    fs::{self, File},
     io::{BufRead, BufReader, Read},
+    os::unix::fs::MetadataExt,
     path::{Path, PathBuf},
     str::FromStr,
     string::ToString,
 };
 
 use comfy_table::{Table, TableComponent};
+use nix::unistd;
 
-use crate::{ExactVersion, RequestedVersion};
+use crate::{
+    completions::{self, Shell},
+    config::Config,
+    doctor::Report,
+    project::{Project, ProjectVersion},
+    shell_init,
+    venv::{VirtualEnv, PYVENV_CFG},
+    ExactVersion, RequestedVersion, SearchContext,
+};
 
-/// The expected directory name for virtual environments.
+/// The default directory name for virtual environments.
+///
+/// Other directory names can be searched for via [`Config::venv_dirs`].
 pub static DEFAULT_VENV_DIR: &str = ".venv";
 
+/// The template for [`Action::PromptInfo`] when none is specified.
+///
+/// See [`Action::from_main`] for the syntax.
+pub static DEFAULT_PROMPT_TEMPLATE: &str = "{version}[ ({venv})]";
+
+/// The environment variable specifying the [`ErrorFormat`].
+pub static ERROR_FORMAT_ENV_VAR: &str = "PY_LAUNCHER_ERROR_FORMAT";
+
+/// How errors are reported.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// A human-readable message (the default).
+    #[default]
+    Text,
+    /// A JSON object (see [`crate::Error::to_json`]).
+    Json,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown error format '{format}'")),
+        }
+    }
+}
+
+impl ErrorFormat {
+    /// Determines the error format, removing any `--error-format=FORMAT` flag
+    /// from `argv`.
+    ///
+    /// The flag must be the first argument for the Launcher (i.e.
+    /// `argv[1]`) and takes precedence over `PY_LAUNCHER_ERROR_FORMAT`.
+    ///
+    /// # Errors
+    ///
+    /// If the format is unknown, [`crate::Error::UnrecognizedArgument`] is
+    /// returned for the flag and [`crate::Error::InvalidConfig`] for the
+    /// environment variable.
+    pub fn from_argv<S: AsRef<OsStr>>(argv: &mut Vec<S>) -> crate::Result<Self> {
+        if let Some(format) = argv
+            .get(1)
+            .and_then(|arg| arg.as_ref().to_str())
+            .and_then(|arg| arg.strip_prefix("--error-format="))
+        {
+            let format = Self::from_str(format).map_err(|_| {
+                crate::Error::UnrecognizedArgument(
+                    PathBuf::from(argv[0].as_ref()),
+                    argv[1].as_ref().to_string_lossy().to_string(),
+                )
+            })?;
+            argv.remove(1);
+            Ok(format)
+        } else {
+            match env::var(ERROR_FORMAT_ENV_VAR) {
+                Ok(format) if !format.is_empty() => Self::from_str(&format).map_err(|message| {
+                    crate::Error::InvalidConfig(ERROR_FORMAT_ENV_VAR.to_string(), message)
+                }),
+                _ => Ok(Self::Text),
+            }
+        }
+    }
+}
+
 /// Represents the possible outcomes based on CLI arguments.
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub enum Action {
@@ -32,8 +112,54 @@ pub enum Action {
     Help(String, PathBuf),
     /// A string listing all found executables on `PATH`.
     ///
+    /// Any virtual environment which would be used by default is listed first.
     /// The string is formatted to be human-readable.
     List(String),
+    /// A string describing the Launcher itself: its version, how it was
+    /// built, the configuration file in effect, and the Python executable it
+    /// would run by default.
+    ///
+    /// The string is formatted to be human-readable.
+    LauncherVersion(String),
+    /// The results of diagnosing the environment the Launcher runs in.
+    Doctor(Report),
+    /// The completion script for a shell.
+    Completions(String),
+    /// A line describing the Python executable which would be run by default,
+    /// meant for a shell prompt.
+    PromptInfo(String),
+    /// A script for a shell to evaluate; either the hook which keeps the
+    /// shell's virtual environment in sync or what the hook runs.
+    ShellInit(String),
+    /// A string explaining which Python executable would be run with what
+    /// arguments, and how the executable was chosen.
+    ///
+    /// The string is formatted to be human-readable.
+    Explain(String),
+    /// Details for creating a virtual environment.
+    CreateVenv {
+        /// The Python executable to create the virtual environment with.
+        executable: PathBuf,
+        /// The directory to create the virtual environment in.
+        dir: PathBuf,
+        /// Whether to replace an existing virtual environment in `dir`.
+        force: bool,
+        /// Whether to upgrade pip in the new virtual environment.
+        upgrade_pip: bool,
+    },
+    /// Details for refreshing a virtual environment found via a search.
+    RefreshVenv {
+        /// The virtual environment to refresh.
+        venv: VirtualEnv,
+        /// The Python executable to recreate the virtual environment with.
+        executable: PathBuf,
+        /// Why the virtual environment needs to be recreated, or [`None`] if
+        /// it is up-to-date.
+        stale_reason: Option<String>,
+        /// A requirements file to install into the (possibly recreated)
+        /// virtual environment.
+        requirements: Option<PathBuf>,
+    },
     /// Details for executing a Python executable.
     Execute {
         /// The Python Launcher used to find the Python executable.
@@ -41,7 +167,19 @@ pub enum Action {
         /// The Python executable to run.
         executable: PathBuf,
         /// Arguments to the executable.
-        args: Vec<String>,
+        args: Vec<OsString>,
+    },
+    /// Details for running a Python executable as a child process which the
+    /// Launcher supervises, instead of replacing the Launcher with it.
+    Supervise {
+        /// The Python Launcher used to find the Python executable.
+        launcher_path: PathBuf,
+        /// The Python executable to run.
+        executable: PathBuf,
+        /// Arguments to the executable.
+        args: Vec<OsString>,
+        /// A shell command to run once the executable exits.
+        post_exit: Option<String>,
     },
 }
 
@@ -54,6 +192,10 @@ impl Action {
     /// The second argument -- `argv.get(1)` -- is used to determine if/what
     /// argument has been provided for the Launcher.
     ///
+    /// The arguments for the Launcher itself must be valid UTF-8, but those
+    /// for the Python executable are passed along untouched (e.g. the path to
+    /// a script using a legacy encoding).
+    ///
     /// # Launcher Arguments
     ///
     /// ## `-h`/`--help`
@@ -67,7 +209,92 @@ impl Action {
     ///
     /// Returns [`Action::List`].
     ///
-    /// The list of executable is gathered via [`crate::all_executables`].
+    /// The list of executable is gathered via [`crate::all_executables`]. If a
+    /// virtual environment would be used when no version is requested, it is
+    /// listed first and marked as the default, with its details coming from its
+    /// `pyvenv.cfg` file (see [`VirtualEnv`]).
+    ///
+    /// ## `--launcher-version`
+    ///
+    /// Returns [`Action::LauncherVersion`].
+    ///
+    /// The Python executable is searched for the same way as when no
+    /// arguments are provided. Failing to find one is reported in the output
+    /// instead of being an error.
+    ///
+    /// ## `--doctor`
+    ///
+    /// Returns [`Action::Doctor`].
+    ///
+    /// Accepts the path to a script whose shebang line is checked as well.
+    /// The configuration is loaded as part of the diagnosis, so an invalid
+    /// configuration is reported instead of being an error. See
+    /// [`Report::run`] for what is checked.
+    ///
+    /// ## `--completions`
+    ///
+    /// Returns [`Action::Completions`].
+    ///
+    /// Accepts the name of the shell to generate the completion script for
+    /// (see [`Shell`]). The version flags are completed based on
+    /// [`crate::all_executables`].
+    ///
+    /// ## `--prompt-info`
+    ///
+    /// Returns [`Action::PromptInfo`].
+    ///
+    /// Accepts a template for the line (defaulting to
+    /// [`DEFAULT_PROMPT_TEMPLATE`]). The Python executable is searched for the
+    /// same way as when no arguments are provided, but it is never executed:
+    /// the version comes from the virtual environment's `pyvenv.cfg` file or
+    /// the executable's file name.
+    ///
+    /// The template supports the placeholders `{version}` (e.g. `3.12`),
+    /// `{major}`, `{minor}`, `{venv}` (the virtual environment's prompt or
+    /// directory name), and `{path}` (the executable). Text in `[` and `]` is
+    /// only included if a placeholder within it is not empty. A `\` includes
+    /// the following character as-is.
+    ///
+    /// ## `--shell-init`
+    ///
+    /// Returns [`Action::ShellInit`].
+    ///
+    /// Accepts the name of the shell (Bash, Zsh, or fish) to return the hook
+    /// script for (see [`shell_init::script`]). If followed by `--hook`, the
+    /// commands which activate or deactivate the virtual environment found via
+    /// a search are returned instead (see [`shell_init::hook`]).
+    ///
+    /// ## `--explain`
+    ///
+    /// Returns [`Action::Explain`].
+    ///
+    /// The remaining arguments are treated as if `--explain` was not specified
+    /// and the same search as for [`Action::Execute`] is performed, but
+    /// instead of running the executable, an explanation of every step of the
+    /// search is returned.
+    ///
+    /// ## `--venv`
+    ///
+    /// Returns [`Action::CreateVenv`].
+    ///
+    /// Accepts a version restriction, `--force`, `--upgrade-pip`, and the
+    /// directory to create the virtual environment in (defaulting to the first
+    /// of [`Config::venv_dirs`]). The executable is searched for the same way
+    /// as for `-m venv`, except that virtual environments are skipped.
+    ///
+    /// ## `--refresh-venv`
+    ///
+    /// Returns [`Action::RefreshVenv`].
+    ///
+    /// Accepts a version restriction and `--requirements`/`-r` followed by the
+    /// path to a requirements file. The virtual environment is searched for
+    /// the same way as when no version restriction is specified (including
+    /// one whose executable no longer exists), while the executable to
+    /// recreate it with is searched for the same way as for `--venv`. Its
+    /// version is only compared to the virtual environment's if requested via
+    /// the version restriction or the project. See
+    /// [`VirtualEnv::stale_reason`] for when a virtual environment is
+    /// considered stale.
     ///
     /// ## Version Restriction
     ///
@@ -78,70 +305,595 @@ impl Action {
     ///
     /// ## No Arguments for the Launcher
     ///
-    /// Returns an [`Action::Execute`] instance.
+    /// Returns an [`Action::Execute`] instance, or an [`Action::Supervise`]
+    /// instance if [`Config::supervise`] is set or [`Config::post_exit`] is
+    /// specified (which also applies to a version restriction).
     ///
     /// As a first step, a check is done for an activated virtual environment
     /// via the `VIRTUAL_ENV` environment variable. If none is set, look for a
-    /// virtual environment in a directory named by [`DEFAULT_VENV_DIR`] in the
-    /// current or any parent directories.
+    /// virtual environment in a directory named by [`Config::venv_dirs`]
+    /// (which defaults to [`DEFAULT_VENV_DIR`]) in the current or any parent
+    /// directories, unless [`Config::venv_search`] is false. The search stops
+    /// after the root of a repository and does not go into `$HOME`, a
+    /// different file system, or any of [`Config::venv_ceiling_dirs`]. A
+    /// virtual environment found this way must be owned by the current user
+    /// (or root) and not be in a directory writable by everyone, unless
+    /// [`Config::venv_allow_unsafe`] is true.
     ///
     /// If no virtual environment is found, a shebang line is searched for in
-    /// the first argument to the Python interpreter. If one is found then it
-    /// is used to (potentially) restrict the requested version searched for.
+    /// the script passed to the Python interpreter (found by parsing the
+    /// arguments the same way CPython does). If one is found which
+    /// launches Python (directly or via `env`, including `env -S`), then the
+    /// version in the interpreter's file name is used to (potentially)
+    /// restrict the requested version searched for. If the shebang specifies an
+    /// absolute path to an executable interpreter (e.g. in a virtual
+    /// environment), then it is used as-is even if a virtual environment is
+    /// activated or found; paths in `/usr/bin` and
+    /// `/usr/local/bin` are only ever treated as a version restriction. Any
+    /// arguments which follow the interpreter in the shebang are passed to the
+    /// Python interpreter before the arguments from the command line (like the
+    /// [Python Launcher for Windows](https://docs.python.org/3/using/windows.html#shebang-lines)).
+    ///
+    /// If the Python interpreter is running a module via `-m`, then the
+    /// nearest project in the current or parent directories (with the same
+    /// boundaries as searching for a virtual environment) is used to restrict
+    /// the requested version. A project is a directory containing a
+    /// `.python-version` file (whose version is used) or a `pyproject.toml`
+    /// file (where the newest version satisfying `requires-python` is used);
+    /// see [`Project`].
     ///
     /// The search for an interpreter proceeds using [`crate::find_executable`].
     ///
     /// # Errors
     ///
-    /// If `-h`, `--help`, or `--list` are specified as the first argument but
-    /// there are other arguments, [`crate::Error::IllegalArgument`] is returned.
+    /// If `-h`, `--help`, `--list`, or `--launcher-version` are specified as
+    /// the first argument but there are other arguments,
+    /// [`crate::Error::IllegalArgument`] is returned.
+    ///
+    /// If `--venv` is given an argument it does not accept,
+    /// [`crate::Error::UnrecognizedArgument`] is returned. If the directory
+    /// for the virtual environment already exists and is not empty without
+    /// `--force` being specified, [`crate::Error::VenvExists`] is returned,
+    /// while [`crate::Error::NotAVenv`] is returned if it is not a virtual
+    /// environment (regardless of `--force`).
+    ///
+    /// If `--doctor` is given more than one argument,
+    /// [`crate::Error::UnrecognizedArgument`] is returned.
+    ///
+    /// If `--completions` is not given exactly one, supported shell,
+    /// [`crate::Error::UnrecognizedArgument`] is returned.
+    ///
+    /// If `--prompt-info` is given more than one argument,
+    /// [`crate::Error::UnrecognizedArgument`] is returned. If the template is
+    /// invalid, [`crate::Error::InvalidTemplate`] is returned.
+    ///
+    /// If `--shell-init` is not given a supported shell optionally followed by
+    /// `--hook`, [`crate::Error::UnrecognizedArgument`] is returned.
+    ///
+    /// If `--refresh-venv` is given an argument it does not accept,
+    /// [`crate::Error::UnrecognizedArgument`] is returned. If no virtual
+    /// environment is found to refresh, [`crate::Error::VenvNotFound`] is
+    /// returned.
     ///
     /// If no executable could be found for [`Action::Help`] or
     /// [`Action::List`], [`crate::Error::NoExecutableFound`] is returned.
     ///
+    /// If the configuration is invalid, [`crate::Error::InvalidConfig`] is
+    /// returned (see [`Config::load`]), except for `-h`, `--help`,
+    /// `--launcher-version`, and `--doctor`.
+    ///
+    /// If a virtual environment found via a search fails the ownership and
+    /// permission checks, [`crate::Error::UnsafeVenv`] is returned. If the
+    /// activated virtual environment lacks an executable,
+    /// [`crate::Error::BrokenVenv`] is returned.
+    ///
     /// # Panics
     ///
     /// - If a [`writeln!`] call fails.
     /// - If the current directory cannot be accessed.
-    pub fn from_main(argv: &[String]) -> crate::Result<Self> {
+    pub fn from_main<S: AsRef<OsStr>>(argv: &[S]) -> crate::Result<Self> {
+        let argv = argv
+            .iter()
+            .map(|arg| arg.as_ref().to_os_string())
+            .collect::<Vec<_>>();
         let launcher_path = PathBuf::from(&argv[0]); // Strip the path to this executable.
+        let flag_args = || utf8_args(&launcher_path, &argv[2..]);
+        if argv.get(1).map(OsString::as_os_str) == Some(OsStr::new("--doctor")) {
+            return doctor_action(&launcher_path, &flag_args()?);
+        }
+        let first_arg = argv.get(1).and_then(|arg| arg.to_str());
+        if let Some(flag @ ("-h" | "--help" | "--list" | "--launcher-version")) = first_arg {
+            if argv.len() > 2 {
+                return Err(crate::Error::IllegalArgument(
+                    launcher_path,
+                    flag.to_string(),
+                ));
+            }
+        }
+        // An invalid configuration must not keep the help or version of the
+        // Launcher from being shown.
+        match first_arg {
+            Some("--launcher-version") => {
+                return Ok(Action::LauncherVersion(launcher_version_message(
+                    &Config::load(),
+                )));
+            }
+            Some("-h" | "--help") => return help_action(&launcher_path),
+            _ => {}
+        }
+        let config = Config::load()?;
 
-        match argv.get(1) {
-            Some(flag) if flag == "-h" || flag == "--help" || flag == "--list" => {
-                if argv.len() > 2 {
-                    Err(crate::Error::IllegalArgument(
+        match first_arg {
+            Some("--list") => {
+                let venv = venv_executable(&config)?
+                    .filter(|venv_path| venv_path.is_file())
+                    .and_then(|venv_path| VirtualEnv::from_executable(&venv_path));
+                Ok(Action::List(list_executables(
+                    &crate::all_executables_with_config(&config),
+                    venv.as_ref(),
+                    &config,
+                )?))
+            }
+            Some("--venv") => create_venv_action(&launcher_path, &flag_args()?, &config),
+            Some("--refresh-venv") => refresh_venv_action(&launcher_path, &flag_args()?, &config),
+            Some("--completions") => completions_action(&launcher_path, &flag_args()?, &config),
+            Some("--prompt-info") => prompt_info_action(&launcher_path, &flag_args()?, &config),
+            Some("--shell-init") => shell_init_action(&launcher_path, &flag_args()?, &config),
+            Some("--explain") => {
+                // Make sure to skip the app path and `--explain`.
+                let mut explanation = Vec::new();
+                let (executable, args) = resolve(&argv[2..], &config, &mut explanation)?;
+                Ok(Action::Explain(explain_message(
+                    &executable,
+                    &args,
+                    &explanation,
+                )))
+            }
+            Some(_) | None => {
+                // Make sure to skip the app path.
+                let (executable, args) = resolve(&argv[1..], &config, &mut Vec::new())?;
+                if config.supervise || config.post_exit.is_some() {
+                    Ok(Action::Supervise {
                         launcher_path,
-                        flag.to_string(),
-                    ))
-                } else if flag == "--list" {
-                    Ok(Action::List(list_executables(&crate::all_executables())?))
+                        executable,
+                        args,
+                        post_exit: config.post_exit,
+                    })
                 } else {
-                    crate::find_executable(RequestedVersion::Any)
-                        .ok_or(crate::Error::NoExecutableFound(RequestedVersion::Any))
-                        .map(|executable_path| {
-                            Action::Help(
-                                help_message(&launcher_path, &executable_path),
-                                executable_path,
-                            )
-                        })
+                    Ok(Action::Execute {
+                        launcher_path,
+                        executable,
+                        args,
+                    })
                 }
             }
-            Some(version) if version_from_flag(version).is_some() => {
-                Ok(Action::Execute {
-                    launcher_path,
-                    // Make sure to skip the app path and version specification.
-                    executable: find_executable(version_from_flag(version).unwrap(), &argv[2..])?,
-                    args: argv[2..].to_vec(),
-                })
+        }
+    }
+}
+
+/// Finds the interpreter to mention in the help message, falling back to the
+/// default configuration if the configuration is invalid.
+fn help_action(launcher_path: &Path) -> crate::Result<Action> {
+    let config = crate::load_config();
+    crate::find_executable_with_config(RequestedVersion::Any, &config)
+        .ok_or_else(|| {
+            crate::Error::NoExecutableFound(
+                RequestedVersion::Any,
+                SearchContext::from_path_with_config(None, &config),
+            )
+        })
+        .map(|executable_path| {
+            Action::Help(
+                help_message(launcher_path, &executable_path),
+                executable_path,
+            )
+        })
+}
+
+/// Converts the arguments following a flag for the Launcher to strings.
+///
+/// # Errors
+///
+/// If an argument is not valid UTF-8, [`crate::Error::UnrecognizedArgument`]
+/// is returned.
+fn utf8_args(launcher_path: &Path, args: &[OsString]) -> crate::Result<Vec<String>> {
+    args.iter()
+        .map(|arg| {
+            arg.to_str().map(str::to_string).ok_or_else(|| {
+                crate::Error::UnrecognizedArgument(
+                    launcher_path.to_path_buf(),
+                    arg.to_string_lossy().to_string(),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Finds the executable to run and the arguments to pass to it based on the
+/// arguments following the path to the Launcher.
+fn resolve(
+    args: &[OsString],
+    config: &Config,
+    explanation: &mut Vec<String>,
+) -> crate::Result<(PathBuf, Vec<OsString>)> {
+    let requested = args
+        .first()
+        .and_then(|arg| arg.to_str())
+        .and_then(|arg| version_from_flag(arg).map(|version| (arg, version)));
+    let (version, python_args) = match requested {
+        Some((flag, version)) => {
+            explanation.push(format!("{version} was requested via {flag}"));
+            // Make sure to skip the version specification.
+            (version, &args[1..])
+        }
+        None => (RequestedVersion::Any, args),
+    };
+    let (executable, shebang_args) =
+        find_executable(version, python_args, config, true, explanation)?;
+    let mut executable_args = shebang_args
+        .into_iter()
+        .map(OsString::from)
+        .collect::<Vec<_>>();
+    executable_args.extend_from_slice(python_args);
+    Ok((executable, executable_args))
+}
+
+/// Parses the arguments to `--venv`.
+fn create_venv_action(
+    launcher_path: &Path,
+    args: &[String],
+    config: &Config,
+) -> crate::Result<Action> {
+    let mut version = RequestedVersion::Any;
+    let mut force = false;
+    let mut upgrade_pip = false;
+    let mut dir: Option<PathBuf> = None;
+
+    for arg in args {
+        if arg == "--force" {
+            force = true;
+        } else if arg == "--upgrade-pip" {
+            upgrade_pip = true;
+        } else if let Some(flag_version) = version_from_flag(arg) {
+            version = flag_version;
+        } else if dir.is_none() && !arg.starts_with('-') {
+            dir = Some(PathBuf::from(arg));
+        } else {
+            return Err(crate::Error::UnrecognizedArgument(
+                launcher_path.to_path_buf(),
+                arg.to_string(),
+            ));
+        }
+    }
+
+    let dir = dir.unwrap_or_else(|| {
+        PathBuf::from(
+            config
+                .venv_dirs
+                .first()
+                .map_or(DEFAULT_VENV_DIR, String::as_str),
+        )
+    });
+    let is_empty_dir = dir
+        .read_dir()
+        .map_or(false, |mut entries| entries.next().is_none());
+    if dir.exists() && !is_empty_dir {
+        if !dir.join(PYVENV_CFG).is_file() {
+            return Err(crate::Error::NotAVenv(dir));
+        } else if !force {
+            return Err(crate::Error::VenvExists(dir));
+        }
+    }
+
+    let venv_args = [
+        OsString::from("-m"),
+        OsString::from("venv"),
+        dir.clone().into_os_string(),
+    ];
+    let (executable, _) = find_executable(version, &venv_args, config, false, &mut Vec::new())?;
+
+    Ok(Action::CreateVenv {
+        executable,
+        dir,
+        force,
+        upgrade_pip,
+    })
+}
+
+/// Parses the arguments to `--refresh-venv`.
+fn refresh_venv_action(
+    launcher_path: &Path,
+    args: &[String],
+    config: &Config,
+) -> crate::Result<Action> {
+    let mut version = RequestedVersion::Any;
+    let mut requirements: Option<PathBuf> = None;
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        if arg == "--requirements" || arg == "-r" {
+            let path = iter.next().ok_or_else(|| {
+                crate::Error::UnrecognizedArgument(launcher_path.to_path_buf(), arg.to_string())
+            })?;
+            requirements = Some(PathBuf::from(path));
+        } else if let Some(flag_version) = version_from_flag(arg) {
+            version = flag_version;
+        } else {
+            return Err(crate::Error::UnrecognizedArgument(
+                launcher_path.to_path_buf(),
+                arg.to_string(),
+            ));
+        }
+    }
+
+    let venv = venv_path_search(config, true)?
+        .and_then(|venv_path| VirtualEnv::from_executable(&venv_path))
+        .ok_or(crate::Error::VenvNotFound)?;
+    let venv_args = [
+        OsString::from("-m"),
+        OsString::from("venv"),
+        venv.root.clone().into_os_string(),
+    ];
+    // Only a requested version is compared against, as otherwise a working
+    // virtual environment would be recreated (losing what is installed in it)
+    // whenever a newer Python is installed.
+    let requested = version != RequestedVersion::Any
+        || nearest_project(config)
+            .map_or(false, |project| project_version(&project, config).is_some());
+    let (executable, _) = find_executable(version, &venv_args, config, false, &mut Vec::new())?;
+    let compared_version = if requested {
+        Some(ExactVersion::from_path(&executable)?)
+    } else {
+        None
+    };
+    let stale_reason = venv.stale_reason(compared_version);
+
+    Ok(Action::RefreshVenv {
+        venv,
+        executable,
+        stale_reason,
+        requirements,
+    })
+}
+
+/// Parses the arguments to `--doctor`.
+fn doctor_action(launcher_path: &Path, args: &[String]) -> crate::Result<Action> {
+    match args {
+        [] => Ok(Action::Doctor(Report::run(Config::load(), None))),
+        [script] => Ok(Action::Doctor(Report::run(
+            Config::load(),
+            Some(Path::new(script)),
+        ))),
+        [_, extra, ..] => Err(crate::Error::UnrecognizedArgument(
+            launcher_path.to_path_buf(),
+            extra.to_string(),
+        )),
+    }
+}
+
+/// Parses the arguments to `--completions`.
+fn completions_action(
+    launcher_path: &Path,
+    args: &[String],
+    config: &Config,
+) -> crate::Result<Action> {
+    match args {
+        [shell] => {
+            let shell = Shell::from_str(shell).map_err(|_| {
+                crate::Error::UnrecognizedArgument(launcher_path.to_path_buf(), shell.to_string())
+            })?;
+            Ok(Action::Completions(completions::script(
+                shell,
+                &crate::all_executables_with_config(config),
+            )))
+        }
+        [] => Err(crate::Error::UnrecognizedArgument(
+            launcher_path.to_path_buf(),
+            "--completions".to_string(),
+        )),
+        [_, extra, ..] => Err(crate::Error::UnrecognizedArgument(
+            launcher_path.to_path_buf(),
+            extra.to_string(),
+        )),
+    }
+}
+
+/// Parses the arguments to `--shell-init`.
+fn shell_init_action(
+    launcher_path: &Path,
+    args: &[String],
+    config: &Config,
+) -> crate::Result<Action> {
+    let unrecognized = |arg: &str| {
+        crate::Error::UnrecognizedArgument(launcher_path.to_path_buf(), arg.to_string())
+    };
+    let (shell_name, hook) = match args {
+        [shell] => (shell, false),
+        [shell, hook] if hook == "--hook" => (shell, true),
+        [] => return Err(unrecognized("--shell-init")),
+        [_, extra, ..] => return Err(unrecognized(extra)),
+    };
+    let shell = Shell::from_str(shell_name)
+        .ok()
+        .filter(|shell| *shell != Shell::Nushell)
+        .ok_or_else(|| unrecognized(shell_name))?;
+
+    if hook {
+        shell_init::hook(shell, config).map(Action::ShellInit)
+    } else {
+        shell_init::script(shell)
+            .map(Action::ShellInit)
+            .ok_or_else(|| unrecognized(shell_name))
+    }
+}
+
+/// Parses the arguments to `--prompt-info`.
+fn prompt_info_action(
+    launcher_path: &Path,
+    args: &[String],
+    config: &Config,
+) -> crate::Result<Action> {
+    let template = match args {
+        [] => DEFAULT_PROMPT_TEMPLATE,
+        [template] => template.as_str(),
+        [_, extra, ..] => {
+            return Err(crate::Error::UnrecognizedArgument(
+                launcher_path.to_path_buf(),
+                extra.to_string(),
+            ))
+        }
+    };
+
+    // The interpreter is the same as for a launch, so probing for pre-releases
+    // (if enabled) still runs interpreters.
+    let (executable, _) = resolve(&[], config, &mut Vec::new())?;
+    let venv = VirtualEnv::from_executable(&executable)
+        .filter(|venv| venv.root.join(PYVENV_CFG).is_file());
+    let version = match &venv {
+        Some(venv) => venv.version,
+        None => ExactVersion::from_path(&executable).ok(),
+    };
+    let venv_name = venv
+        .map(|venv| {
+            venv.prompt.unwrap_or_else(|| {
+                venv.root
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            })
+        })
+        .unwrap_or_default();
+
+    render_template(template, |placeholder| match placeholder {
+        "version" => Ok(version.map(|v| v.to_string()).unwrap_or_default()),
+        "major" => Ok(version.map(|v| v.major.to_string()).unwrap_or_default()),
+        "minor" => Ok(version.map(|v| v.minor.to_string()).unwrap_or_default()),
+        "venv" => Ok(venv_name.clone()),
+        "path" => Ok(executable.display().to_string()),
+        _ => Err(format!("unknown placeholder `{{{placeholder}}}`")),
+    })
+    .map(|line| Action::PromptInfo(line + "\n"))
+    .map_err(|message| crate::Error::InvalidTemplate(template.to_string(), message))
+}
+
+/// Renders `template` by replacing every `{placeholder}` with its value from
+/// `lookup`.
+///
+/// Text within `[` and `]` is only included if at least one placeholder
+/// within it is not empty. A `\` includes the following character as-is.
+fn render_template(
+    template: &str,
+    lookup: impl Fn(&str) -> Result<String, String>,
+) -> Result<String, String> {
+    let mut rendered = String::new();
+    // The text of the current optional section and whether to include it.
+    let mut section: Option<(String, bool)> = None;
+    let mut chars = template.chars();
+
+    while let Some(c) = chars.next() {
+        let text = match c {
+            '\\' => chars
+                .next()
+                .ok_or_else(|| "`\\` at the end".to_string())?
+                .to_string(),
+            '[' if section.is_some() => return Err("`[` cannot be nested".to_string()),
+            '[' => {
+                section = Some((String::new(), false));
+                continue;
             }
-            Some(_) | None => Ok(Action::Execute {
-                launcher_path,
-                // Make sure to skip the app path.
-                executable: find_executable(RequestedVersion::Any, &argv[1..])?,
-                args: argv[1..].to_vec(),
-            }),
+            ']' => {
+                match section.take() {
+                    Some((text, true)) => rendered.push_str(&text),
+                    Some((_, false)) => {}
+                    None => return Err("`]` without a `[`".to_string()),
+                }
+                continue;
+            }
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => return Err("`{` without a `}`".to_string()),
+                    }
+                }
+                let value = lookup(&placeholder)?;
+                if let Some((_, include)) = &mut section {
+                    *include |= !value.is_empty();
+                }
+                value
+            }
+            '}' => return Err("`}` without a `{`".to_string()),
+            c => c.to_string(),
+        };
+
+        match &mut section {
+            Some((section_text, _)) => section_text.push_str(&text),
+            None => rendered.push_str(&text),
+        }
+    }
+
+    if section.is_some() {
+        Err("`[` without a `]`".to_string())
+    } else {
+        Ok(rendered)
+    }
+}
+
+fn launcher_version_message(loaded: &crate::Result<Config>) -> String {
+    let mut message = String::new();
+    writeln!(
+        message,
+        "Python Launcher for Unix {}",
+        env!("CARGO_PKG_VERSION")
+    )
+    .unwrap();
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+    writeln!(
+        message,
+        "Build: {}-{} ({profile})",
+        env::consts::ARCH,
+        env::consts::OS
+    )
+    .unwrap();
+
+    let default_config = Config::default();
+    let (config, config_file) = match loaded {
+        Ok(config) => {
+            let config_file = match (&config.path, Config::file_path()) {
+                (Some(path), _) => path.display().to_string(),
+                (None, Some(path)) => format!("{} (not found)", path.display()),
+                (None, None) => "none".to_string(),
+            };
+            (config, config_file)
         }
+        Err(error) => (&default_config, format!("ignored ({error})")),
+    };
+    writeln!(message, "Configuration file: {config_file}").unwrap();
+
+    let interpreter = match resolve(&[], config, &mut Vec::new()) {
+        Ok((executable, _)) => executable.display().to_string(),
+        Err(error) => format!("none ({error})"),
+    };
+    writeln!(message, "Default interpreter: {interpreter}").unwrap();
+    message
+}
+
+fn explain_message(executable: &Path, args: &[OsString], explanation: &[String]) -> String {
+    let mut message = String::new();
+    let printable_executable = executable.display();
+    writeln!(message, "Executable: {printable_executable}").unwrap();
+    writeln!(message, "Arguments: {args:?}").unwrap();
+    writeln!(message, "How the executable was chosen:").unwrap();
+    for (index, step) in explanation.iter().enumerate() {
+        writeln!(message, "  {}. {step}", index + 1).unwrap();
     }
+    message
 }
 
 fn help_message(launcher_path: &Path, executable_path: &Path) -> String {
@@ -169,9 +921,16 @@ fn version_from_flag(arg: &str) -> Option<RequestedVersion> {
     }
 }
 
-fn list_executables(executables: &HashMap<ExactVersion, PathBuf>) -> crate::Result<String> {
-    if executables.is_empty() {
-        return Err(crate::Error::NoExecutableFound(RequestedVersion::Any));
+fn list_executables(
+    executables: &HashMap<ExactVersion, PathBuf>,
+    venv: Option<&VirtualEnv>,
+    config: &Config,
+) -> crate::Result<String> {
+    if executables.is_empty() && venv.is_none() {
+        return Err(crate::Error::NoExecutableFound(
+            RequestedVersion::Any,
+            SearchContext::from_path_with_config(None, config),
+        ));
     }
 
     let mut executable_pairs = Vec::from_iter(executables);
@@ -186,17 +945,46 @@ fn list_executables(executables: &HashMap<ExactVersion, PathBuf>) -> crate::Resu
     // parse the output.
     table.set_style(TableComponent::VerticalLines, '│');
 
+    let mut venv_description = None;
+    if let Some(venv) = venv {
+        let version = venv
+            .version
+            .map_or_else(|| "?".to_string(), |version| version.to_string());
+        table.add_row(vec![version, venv.python().display().to_string()]);
+        venv_description = Some(match venv.base_executable() {
+            Some(base_executable) => {
+                let printable_base = base_executable.display();
+                format!("default; virtual environment of {printable_base}")
+            }
+            None => "default; virtual environment".to_string(),
+        });
+    }
+
     for (version, path) in executable_pairs {
-        table.add_row(vec![version.to_string(), path.display().to_string()]);
+        // Executables are only run to find their release level if asked to.
+        let version = if crate::is_prerelease(*version, path, config) {
+            format!("{version} (pre-release)")
+        } else {
+            version.to_string()
+        };
+        table.add_row(vec![version, path.display().to_string()]);
     }
 
-    Ok(table.to_string() + "\n")
+    let mut output = table.to_string() + "\n";
+    // Only the virtual environment gets a third column so every other row
+    // stays at two columns.
+    if let Some(description) = venv_description {
+        let first_line_end = output.find('\n').unwrap();
+        output.insert_str(first_line_end, &format!("│ {description}"));
+    }
+
+    Ok(output)
 }
 
-fn relative_venv_path(add_default: bool) -> PathBuf {
+fn relative_venv_path(venv_dir: Option<&str>) -> PathBuf {
     let mut path = PathBuf::new();
-    if add_default {
-        path.push(DEFAULT_VENV_DIR);
+    if let Some(dir) = venv_dir {
+        path.push(dir);
     }
     path.push("bin");
     path.push("python");
@@ -208,10 +996,10 @@ fn relative_venv_path(add_default: bool) -> PathBuf {
 /// A virtual environment is determined to be activated based on the
 /// existence of the `VIRTUAL_ENV` environment variable.
 fn venv_executable_path(venv_root: &str) -> PathBuf {
-    PathBuf::from(venv_root).join(relative_venv_path(false))
+    PathBuf::from(venv_root).join(relative_venv_path(None))
 }
 
-fn activated_venv() -> Option<PathBuf> {
+pub(crate) fn activated_venv() -> Option<PathBuf> {
     log::info!("Checking for VIRTUAL_ENV environment variable");
     env::var_os("VIRTUAL_ENV").map(|venv_root| {
         log::debug!("VIRTUAL_ENV set to {venv_root:?}");
@@ -219,92 +1007,611 @@ fn activated_venv() -> Option<PathBuf> {
     })
 }
 
-fn venv_path_search() -> Option<PathBuf> {
-    if env::current_dir().is_err() {
+/// Files or directories which mark the root of a repository.
+static VCS_MARKERS: [&str; 2] = [".git", ".hg"];
+
+/// Where a search through the current and parent directories stops.
+///
+/// The search never goes into `$HOME`, a ceiling directory, or a different
+/// file system than where it started (unless starting from there), and it
+/// stops after searching the root of a repository.
+struct SearchBoundaries {
+    start: PathBuf,
+    home: Option<PathBuf>,
+    ceiling_dirs: Vec<PathBuf>,
+    device: Option<u64>,
+}
+
+impl SearchBoundaries {
+    fn new(start: &Path, config: &Config) -> Self {
+        let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+        Self {
+            start: start.to_path_buf(),
+            home: env::var_os("HOME")
+                .filter(|home| !home.is_empty())
+                .map(|home| canonical(Path::new(&home))),
+            ceiling_dirs: config
+                .venv_ceiling_dirs
+                .iter()
+                .map(|dir| canonical(dir))
+                .collect(),
+            device: fs::metadata(start).ok().map(|metadata| metadata.dev()),
+        }
+    }
+
+    /// Checks if the search should stop before searching `path`.
+    fn stops_before(&self, path: &Path) -> bool {
+        let printable_path = path.display();
+        if path == self.start {
+            false
+        } else if self.home.as_deref() == Some(path) {
+            log::info!("Stopping the search at the home directory {printable_path}");
+            true
+        } else if self.ceiling_dirs.iter().any(|dir| dir == path) {
+            log::info!("Stopping the search at the ceiling directory {printable_path}");
+            true
+        } else if self.device.is_some()
+            && fs::metadata(path).ok().map(|metadata| metadata.dev()) != self.device
+        {
+            log::info!("Stopping the search at the file system boundary {printable_path}");
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Checks if the search should stop after searching `path`.
+    fn stops_after(&self, path: &Path) -> bool {
+        match VCS_MARKERS.iter().find(|marker| path.join(marker).exists()) {
+            Some(marker) => {
+                let printable_path = path.display();
+                log::info!(
+                    "Stopping the search at the repository root {printable_path} ({marker})"
+                );
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The directories to search, in order.
+    fn ancestors(&self) -> impl Iterator<Item = &Path> {
+        let mut stopped = false;
+        self.start
+            .ancestors()
+            .take_while(move |path| !self.stops_before(path))
+            .take_while(move |path| {
+                // Include the directory which triggers the stop.
+                let searchable = !stopped;
+                stopped = self.stops_after(path);
+                searchable
+            })
+    }
+}
+
+/// Checks if a path is owned by someone who can be trusted.
+///
+/// Only the current user and root are trusted.
+fn check_owner(path: &Path, owner: u32, euid: u32) -> std::result::Result<(), String> {
+    if owner == euid || owner == 0 {
+        Ok(())
+    } else {
+        let printable_path = path.display();
+        Err(format!(
+            "{printable_path} is owned by another user (UID {owner})"
+        ))
+    }
+}
+
+/// Checks that a directory is not writable by everyone.
+///
+/// Directories with the sticky bit set (e.g. `/tmp`) are allowed as only the
+/// owner of a file may then replace it.
+pub(crate) fn check_permissions(path: &Path, mode: u32) -> std::result::Result<(), String> {
+    if mode & 0o002 != 0 && mode & 0o1000 == 0 {
+        let printable_path = path.display();
+        Err(format!(
+            "{printable_path} is writable by everyone without the sticky bit set"
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that a virtual environment found via a search can be trusted to be
+/// executed.
+///
+/// The virtual environment's executable, `bin` directory, and root directory
+/// must all be owned by the current user or root, and no directory containing
+/// the executable may be writable by everyone (unless the sticky bit is set).
+fn check_venv_safety(venv_executable: &Path) -> std::result::Result<(), String> {
+    let euid = unistd::geteuid().as_raw();
+    let bin_dir = venv_executable.parent().unwrap_or(venv_executable);
+    let venv_root = bin_dir.parent().unwrap_or(bin_dir);
+
+    for path in [venv_executable, bin_dir, venv_root] {
+        let metadata = fs::symlink_metadata(path).map_err(|error| error.to_string())?;
+        check_owner(path, metadata.uid(), euid)?;
+    }
+
+    for dir in bin_dir.ancestors() {
+        let metadata = fs::metadata(dir).map_err(|error| error.to_string())?;
+        check_permissions(dir, metadata.mode())?;
+    }
+
+    Ok(())
+}
+
+/// Searches the current and parent directories for a virtual environment.
+///
+/// If `include_broken` is true then a virtual environment whose executable is
+/// a dangling symlink (e.g. its base interpreter was uninstalled) is also
+/// found.
+pub(crate) fn venv_path_search(
+    config: &Config,
+    include_broken: bool,
+) -> crate::Result<Option<PathBuf>> {
+    if !config.venv_search {
+        log::info!("Searching for a venv is disabled");
+        Ok(None)
+    } else if env::current_dir().is_err() {
         log::warn!("current working directory is invalid");
-        None
+        Ok(None)
     } else {
         let cwd = env::current_dir().unwrap();
         let printable_cwd = cwd.display();
-        log::info!("Searching for a venv in {printable_cwd} and parent directories");
-        cwd.ancestors().find_map(|path| {
-            let venv_path = path.join(relative_venv_path(true));
-            let printable_venv_path = venv_path.display();
-            log::info!("Checking {printable_venv_path}");
-            // bool::then_some() makes more sense, but still experimental.
-            venv_path.is_file().then_some(venv_path)
+        let venv_dirs = &config.venv_dirs;
+        log::info!(
+            "Searching for a venv in {printable_cwd} and parent directories using {venv_dirs:?}"
+        );
+        let boundaries = SearchBoundaries::new(&cwd, config);
+        for path in boundaries.ancestors() {
+            for venv_dir in venv_dirs {
+                let venv_path = path.join(relative_venv_path(Some(venv_dir)));
+                let printable_venv_path = venv_path.display();
+                log::info!("Checking {printable_venv_path}");
+                let is_broken = include_broken && venv_path.symlink_metadata().is_ok();
+                if !venv_path.is_file() && !is_broken {
+                    continue;
+                } else if config.venv_allow_unsafe {
+                    log::info!("Skipping the safety check of {printable_venv_path}");
+                } else if let Err(reason) = check_venv_safety(&venv_path) {
+                    return Err(crate::Error::UnsafeVenv(venv_path, reason));
+                }
+
+                return Ok(Some(venv_path));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+fn venv_executable(config: &Config) -> crate::Result<Option<PathBuf>> {
+    match activated_venv() {
+        Some(venv_path) => Ok(Some(venv_path)),
+        None => venv_path_search(config, false),
+    }
+}
+
+/// Directories whose Python interpreters in a shebang are treated as a version
+/// restriction instead of a specific interpreter.
+///
+/// This follows the "virtual commands" of the
+/// [Python Launcher for Windows](https://docs.python.org/3/using/windows.html#shebang-lines).
+static VIRTUAL_SHEBANG_DIRS: [&str; 2] = ["/usr/bin", "/usr/local/bin"];
+
+/// A shebang line which launches Python.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Shebang {
+    /// The interpreter named by the shebang, after any `env` indirection
+    /// (e.g. `/usr/bin/python3` or `python3.11`).
+    interpreter: PathBuf,
+    /// The version restriction based on the interpreter's file name.
+    pub(crate) requested_version: RequestedVersion,
+    /// Arguments for the interpreter which follow it in the shebang.
+    args: Vec<String>,
+}
+
+impl Shebang {
+    /// Parses what follows `#!` on the first line of a file.
+    ///
+    /// Returns [`None`] if the interpreter is not Python.
+    fn parse(line: &str) -> Option<Self> {
+        // Whitespace between `#!` and the path is allowed.
+        let (command, rest) = split_first_word(line.trim());
+        let mut words = if Path::new(command).file_name()? == "env" {
+            env_command(rest)
+        } else {
+            let mut words = vec![command.to_string()];
+            words.extend(split_args(rest));
+            words
+        }
+        .into_iter();
+
+        let interpreter = PathBuf::from(words.next()?);
+        let version = interpreter
+            .file_name()?
+            .to_str()?
+            .strip_prefix("python")?
+            .to_string();
+        log::debug!("Found version: {version}");
+        let requested_version = RequestedVersion::from_str(&version).ok()?;
+
+        Some(Self {
+            interpreter,
+            requested_version,
+            args: words.collect(),
         })
     }
+
+    /// The interpreter if it is an absolute path outside of
+    /// [`VIRTUAL_SHEBANG_DIRS`], i.e. it names a specific interpreter to use.
+    pub(crate) fn absolute_interpreter(&self) -> Option<&Path> {
+        let dir = self.interpreter.parent()?;
+        (self.interpreter.is_absolute()
+            && !VIRTUAL_SHEBANG_DIRS.iter().any(|d| dir == Path::new(d)))
+        .then_some(self.interpreter.as_path())
+    }
+}
+
+pub(crate) fn is_executable(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|metadata| metadata.is_file() && metadata.mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+fn split_first_word(line: &str) -> (&str, &str) {
+    match line.split_once(char::is_whitespace) {
+        Some((first, rest)) => (first, rest.trim_start()),
+        None => (line, ""),
+    }
 }
 
-fn venv_executable() -> Option<PathBuf> {
-    activated_venv().or_else(venv_path_search)
+/// Splits the arguments of `env` into the command to run and its arguments.
+///
+/// Both `-S`/`--split-string` and environment variable assignments are
+/// supported. Any other option is skipped.
+fn env_command(args: &str) -> Vec<String> {
+    let mut rest = args;
+    loop {
+        let (word, after) = split_first_word(rest);
+        if word == "-S" || word == "--split-string" {
+            rest = after;
+        } else if let Some(attached) = word
+            .strip_prefix("--split-string=")
+            .or_else(|| word.strip_prefix("-S"))
+        {
+            // What's attached to the option is the start of the command.
+            rest = &rest[word.len() - attached.len()..];
+        } else if word.starts_with('-') || word.contains('=') {
+            rest = after;
+        } else {
+            return split_args(rest);
+        }
+    }
+}
+
+/// Splits a string into arguments on whitespace, respecting quotes and
+/// backslash escapes.
+fn split_args(args: &str) -> Vec<String> {
+    let mut split = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = args.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            (c, Some(q)) if c == q => quote = None,
+            ('\\', Some('"') | None) => {
+                if let Some(escaped) = chars.next() {
+                    current.get_or_insert_with(String::new).push(escaped);
+                }
+            }
+            (c, Some(_)) => current.get_or_insert_with(String::new).push(c),
+            ('\'' | '"', None) => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (c, None) if c.is_whitespace() => split.extend(current.take()),
+            (c, None) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    split.extend(current);
+
+    split
 }
 
+/// The most bytes read from the start of a file when looking for a shebang.
+///
+/// This is much longer than what operating systems support to not be the
+/// limiting factor while still not reading in an entire file which happens to
+/// lack newlines.
+pub(crate) const MAX_SHEBANG_LENGTH: u64 = 4096;
+
+/// The UTF-8 encoding of U+FEFF/"Byte Order Mark".
+pub(crate) const UTF8_BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+
 // https://en.m.wikipedia.org/wiki/Shebang_(Unix)
-fn parse_python_shebang(reader: &mut impl Read) -> Option<RequestedVersion> {
-    let mut shebang_buffer = [0; 2];
+pub(crate) fn parse_python_shebang(reader: &mut impl Read) -> Option<Shebang> {
     log::info!("Looking for a Python-related shebang");
-    if reader.read(&mut shebang_buffer).is_err() || shebang_buffer != [0x23, 0x21] {
-        // Doesn't start w/ `#!` in ASCII/UTF-8.
-        log::debug!("No '#!' at the start of the first line of the file");
+    let mut buffered_reader = BufReader::new(reader.take(MAX_SHEBANG_LENGTH));
+    let mut first_line = Vec::new();
+
+    if buffered_reader.read_until(b'\n', &mut first_line).is_err() {
+        log::debug!("Can't read first line of the file");
+        return None;
+    } else if first_line.len() as u64 == MAX_SHEBANG_LENGTH && !first_line.ends_with(b"\n") {
+        log::debug!("First line of the file is longer than {MAX_SHEBANG_LENGTH} bytes");
         return None;
     }
 
-    let mut buffered_reader = BufReader::new(reader);
-    let mut first_line = String::new();
+    // Editors on Windows may prepend a BOM.
+    let line = first_line.strip_prefix(UTF8_BOM).unwrap_or(&first_line);
+    let Some(shebang) = line.strip_prefix(b"#!") else {
+        // Doesn't start w/ `#!` in ASCII/UTF-8.
+        log::debug!("No '#!' at the start of the first line of the file");
+        return None;
+    };
 
-    if buffered_reader.read_line(&mut first_line).is_err() {
-        log::debug!("Can't read first line of the file");
+    let Ok(shebang) = std::str::from_utf8(shebang) else {
+        log::debug!("First line of the file is not UTF-8");
         return None;
     };
+    // Neither the operating system nor an argument can handle a NUL byte.
+    if shebang.contains('\0') {
+        log::debug!("First line of the file contains a NUL byte");
+        return None;
+    }
 
-    // Whitespace between `#!` and the path is allowed.
-    let line = first_line.trim();
+    // Handles both `\n` and `\r\n`.
+    let shebang = shebang.trim_end_matches(['\r', '\n']);
+    log::debug!("Found shebang: {shebang:?}");
+    Shebang::parse(shebang)
+}
 
-    let accepted_paths = [
-        "python",
-        "/usr/bin/python",
-        "/usr/local/bin/python",
-        "/usr/bin/env python",
-    ];
+/// What the Python interpreter will run based on its command-line arguments.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PythonTarget {
+    /// A script file, e.g. `python script.py`.
+    Script(PathBuf),
+    /// A module via `-m`, e.g. `python -m pip`.
+    Module(String),
+    /// A command via `-c`, e.g. `python -c "print('Hello')"`.
+    Command,
+    /// Standard input (which includes the REPL).
+    Stdin,
+}
+
+/// CPython's short options which take an argument, besides `-c` and `-m`.
+static SHORT_OPTIONS_WITH_ARG: [char; 2] = ['W', 'X'];
+
+/// CPython's short options which do not take an argument.
+static SHORT_OPTIONS: [char; 18] = [
+    'b', 'B', 'd', 'E', 'h', 'i', 'I', 'O', 'P', 'q', 'R', 's', 'S', 'u', 'v', 'V', 'x', '?',
+];
 
-    for acceptable_path in &accepted_paths {
-        if !line.starts_with(acceptable_path) {
-            continue;
+/// CPython's long options which take an argument.
+static LONG_OPTIONS_WITH_ARG: [&str; 1] = ["--check-hash-based-pycs"];
+
+impl PythonTarget {
+    /// Determines the target by parsing the arguments the same way CPython
+    /// does.
+    ///
+    /// Short options may be combined (e.g. `-uB`) and the argument to an
+    /// option may be attached (e.g. `-Wdefault` or `-mpip`). Anything which
+    /// CPython would reject is treated as [`PythonTarget::Stdin`] as no script
+    /// will end up being run.
+    fn from_args(args: &[OsString]) -> Self {
+        let mut iter = args.iter();
+
+        while let Some(os_arg) = iter.next() {
+            // Options are ASCII, so a lossy conversion only affects a path to
+            // a script or the argument to an option.
+            let arg = os_arg.to_string_lossy();
+            if arg == "--" {
+                return iter
+                    .next()
+                    .map_or(Self::Stdin, |script| Self::Script(PathBuf::from(script)));
+            } else if arg == "-" {
+                return Self::Stdin;
+            } else if arg.starts_with("--") {
+                if LONG_OPTIONS_WITH_ARG.contains(&arg.as_ref()) {
+                    iter.next();
+                } else if !arg.starts_with("--help") && arg != "--version" {
+                    log::debug!("Unrecognized option {arg:?}");
+                    return Self::Stdin;
+                }
+            } else if let Some(flags) = arg.strip_prefix('-') {
+                for (index, flag) in flags.char_indices() {
+                    let attached = &flags[index + flag.len_utf8()..];
+                    if flag == 'c' {
+                        return Self::Command;
+                    } else if flag == 'm' {
+                        let module = if attached.is_empty() {
+                            iter.next()
+                                .map(|module| module.to_string_lossy().to_string())
+                        } else {
+                            Some(attached.to_string())
+                        };
+                        return module.map_or(Self::Stdin, Self::Module);
+                    } else if SHORT_OPTIONS_WITH_ARG.contains(&flag) {
+                        if attached.is_empty() {
+                            iter.next();
+                        }
+                        break;
+                    } else if !SHORT_OPTIONS.contains(&flag) {
+                        log::debug!("Unrecognized option {flag:?} in {arg:?}");
+                        return Self::Stdin;
+                    }
+                }
+            } else {
+                return Self::Script(PathBuf::from(os_arg));
+            }
         }
 
-        log::debug!("Found shebang: {acceptable_path}");
-        let version = line[acceptable_path.len()..].to_string();
-        log::debug!("Found version: {version}");
-        return RequestedVersion::from_str(&version).ok();
+        Self::Stdin
+    }
+}
+
+/// Finds the nearest project in the current or parent directories.
+fn nearest_project(config: &Config) -> Option<Project> {
+    let cwd = env::current_dir().ok()?;
+    let printable_cwd = cwd.display();
+    log::info!("Searching for a project in {printable_cwd} and parent directories");
+    let boundaries = SearchBoundaries::new(&cwd, config);
+    for path in boundaries.ancestors() {
+        if let Some(project) = Project::from_dir(path) {
+            return Some(project);
+        }
     }
 
     None
 }
 
-fn find_executable(version: RequestedVersion, args: &[String]) -> crate::Result<PathBuf> {
+/// Determines the version to search for based on what a project specifies.
+///
+/// As `requires-python` may be satisfied by multiple versions, the newest
+/// version found which satisfies it is used.
+fn project_version(project: &Project, config: &Config) -> Option<RequestedVersion> {
+    match &project.version {
+        ProjectVersion::Pinned(version) => Some(*version),
+        ProjectVersion::Requires(requires_python) => crate::all_executables_with_config(config)
+            .into_keys()
+            .filter(|version| requires_python.contains(*version))
+            .max()
+            .map(|version| RequestedVersion::Exact(version.major, version.minor)),
+        ProjectVersion::Unspecified => None,
+    }
+}
+
+/// Finds the executable to run along with any arguments from a shebang to pass
+/// to it before the arguments provided on the command line.
+///
+/// Virtual environments are only considered if `use_venv` is true. Every step
+/// taken to find the executable is recorded in `explanation`.
+fn find_executable(
+    version: RequestedVersion,
+    args: &[OsString],
+    config: &Config,
+    use_venv: bool,
+    explanation: &mut Vec<String>,
+) -> crate::Result<(PathBuf, Vec<String>)> {
     let mut requested_version = version;
+    let mut requested_by: Option<String> = None;
     let mut chosen_path: Option<PathBuf> = None;
+    let mut shebang_args = Vec::new();
 
-    if requested_version == RequestedVersion::Any {
-        if let Some(venv_path) = venv_executable() {
-            chosen_path = Some(venv_path);
-        } else if !args.is_empty() {
-            // Using the first argument because it's the simplest and sanest.
-            // We can't use the last argument because that could actually be an argument
-            // to the Python module being executed. This is the same reason we can't go
-            // searching for the first/last file path that we find. The only safe way to
-            // get the file path regardless of its position is to replicate Python's arg
-            // parsing and that's a **lot** of work for little gain. Hence we only care
-            // about the first argument.
-            let possible_file = &args[0];
+    let target =
+        (requested_version == RequestedVersion::Any).then(|| PythonTarget::from_args(args));
+    let script_shebang = match &target {
+        Some(PythonTarget::Script(possible_file)) => {
             log::info!("Checking {possible_file:?} for a shebang");
-            if let Ok(mut open_file) = File::open(possible_file) {
-                if let Some(shebang_version) = parse_python_shebang(&mut open_file) {
-                    requested_version = shebang_version;
+            File::open(possible_file)
+                .ok()
+                .and_then(|mut open_file| parse_python_shebang(&mut open_file))
+        }
+        _ => None,
+    };
+
+    // An executable interpreter specified by a shebang line is authoritative,
+    // even over a virtual environment.
+    if let (Some(PythonTarget::Script(possible_file)), Some(shebang)) = (&target, &script_shebang) {
+        if let Some(interpreter) = shebang
+            .absolute_interpreter()
+            .filter(|interpreter| is_executable(interpreter))
+        {
+            let printable_interpreter = interpreter.display();
+            explanation.push(format!(
+                "The shebang line of {possible_file:?} specifies {printable_interpreter}"
+            ));
+            chosen_path = Some(interpreter.to_path_buf());
+            shebang_args = shebang.args.clone();
+        }
+    }
+
+    if chosen_path.is_none() && use_venv && requested_version == RequestedVersion::Any {
+        if let Some(venv_path) = activated_venv() {
+            if !venv_path.is_file() {
+                return Err(crate::Error::BrokenVenv(venv_path));
+            }
+            let printable_venv_path = venv_path.display();
+            explanation.push(format!(
+                "Using the activated virtual environment ({printable_venv_path})"
+            ));
+            chosen_path = Some(venv_path);
+        } else if let Some(venv_path) = venv_path_search(config, false)? {
+            let printable_venv_path = venv_path.display();
+            explanation.push(format!(
+                "Using the virtual environment found at {printable_venv_path}"
+            ));
+            chosen_path = Some(venv_path);
+        } else {
+            explanation.push("No virtual environment is activated or was found".to_string());
+        }
+    }
+
+    if chosen_path.is_none() {
+        match target {
+            Some(PythonTarget::Script(possible_file)) => {
+                if let Some(shebang) = script_shebang {
+                    match shebang.absolute_interpreter() {
+                        Some(interpreter) => {
+                            let printable_interpreter = interpreter.display();
+                            let shebang_version = shebang.requested_version;
+                            log::info!("{printable_interpreter} is not an executable; searching for {shebang_version} instead");
+                            explanation.push(format!("The shebang line of {possible_file:?} specifies {printable_interpreter}, which is not an executable, so searching for {shebang_version}"));
+                        }
+                        None => {
+                            let shebang_version = shebang.requested_version;
+                            explanation.push(format!(
+                                "The shebang line of {possible_file:?} requests {shebang_version}"
+                            ));
+                        }
+                    }
+                    requested_version = shebang.requested_version;
+                    requested_by = Some(format!("the shebang line of {possible_file:?}"));
+                    shebang_args = shebang.args;
+                } else {
+                    explanation.push(format!(
+                        "{possible_file:?} has no shebang line which launches Python"
+                    ));
+                }
+            }
+            Some(PythonTarget::Module(module)) => {
+                match nearest_project(config) {
+                    Some(project) => {
+                        let printable_project_file = project.file.display();
+                        match project_version(&project, config) {
+                            Some(version) => {
+                                explanation.push(format!("Running module {module:?} for the project specified by {printable_project_file}, which requests {version}"));
+                                requested_version = version;
+                                requested_by = Some(printable_project_file.to_string());
+                            }
+                            // Falling back to any version would ignore what the
+                            // project requires.
+                            None if matches!(project.version, ProjectVersion::Requires(_)) => {
+                                return Err(crate::Error::NoExecutableFound(
+                                    requested_version,
+                                    SearchContext::from_path_with_config(
+                                        Some(printable_project_file.to_string()),
+                                        config,
+                                    ),
+                                ));
+                            }
+                            None => {
+                                log::warn!("{printable_project_file} does not specify a usable Python version");
+                                explanation.push(format!("Running module {module:?} for the project specified by {printable_project_file}, which does not specify a usable Python version"));
+                            }
+                        }
+                    }
+                    None => explanation.push(format!(
+                        "Running module {module:?}, but no project was found"
+                    )),
                 }
             }
+            Some(PythonTarget::Command | PythonTarget::Stdin) | None => {}
         }
     }
 
@@ -315,23 +1622,54 @@ fn find_executable(version: RequestedVersion, args: &[String]) -> crate::Result<
                 if !env_var_value.is_empty() {
                     log::debug!("{env_var} = '{env_var_value}'");
                     let env_requested_version = RequestedVersion::from_str(&env_var_value)?;
+                    explanation.push(format!(
+                        "{env_var} is set to {env_var_value:?}, which requests {env_requested_version}"
+                    ));
                     requested_version = env_requested_version;
+                    requested_by = Some(env_var);
                 }
             } else {
                 log::info!("{env_var} not set");
             };
         }
 
-        if let Some(executable_path) = crate::find_executable(requested_version) {
+        for (excluded_path, exclusion) in crate::excluded_executables(config) {
+            let printable_excluded_path = excluded_path.display();
+            explanation.push(format!(
+                "{printable_excluded_path} is excluded as it matches {exclusion:?}"
+            ));
+        }
+        if let Some(executable_path) = crate::find_executable_with_config(requested_version, config)
+        {
+            let printable_executable_path = executable_path.display();
+            let preference = if config.prefer_system
+                || config.stable.is_some()
+                || config.probe_prereleases
+                || !config.prereleases.is_empty()
+            {
+                "preferred"
+            } else {
+                "newest"
+            };
+            explanation.push(format!(
+                "The {preference} executable on PATH for {requested_version} is {printable_executable_path}"
+            ));
             chosen_path = Some(executable_path);
         }
     }
 
-    chosen_path.ok_or(crate::Error::NoExecutableFound(requested_version))
+    chosen_path.map(|path| (path, shebang_args)).ok_or_else(|| {
+        crate::Error::NoExecutableFound(
+            requested_version,
+            SearchContext::from_path_with_config(requested_by, config),
+        )
+    })
 }
 
 #[cfg(test)]
 mod tests {
+    use std::os::unix::{ffi::OsStringExt, fs::PermissionsExt};
+
     use test_case::test_case;
 
     use super::*;
@@ -352,6 +1690,30 @@ mod tests {
         version_from_flag(flag)
     }
 
+    #[test_case("{version}" => Ok("3.12".to_string()) ; "placeholder")]
+    #[test_case("py {major}/{minor}" => Ok("py 3/12".to_string()) ; "multiple placeholders")]
+    #[test_case("{version}[ ({venv})]" => Ok("3.12 (.venv)".to_string()) ; "included section")]
+    #[test_case("{version}[ ({empty})]" => Ok("3.12".to_string()) ; "omitted section")]
+    #[test_case("[{empty}{venv}]" => Ok(".venv".to_string()) ; "partially empty section")]
+    #[test_case(r"\{\[\\\]\}" => Ok(r"{[\]}".to_string()) ; "escapes")]
+    #[test_case("{unknown}" => Err("unknown placeholder `{unknown}`".to_string()) ; "unknown placeholder")]
+    #[test_case("{version" => Err("`{` without a `}`".to_string()) ; "unclosed placeholder")]
+    #[test_case("version}" => Err("`}` without a `{`".to_string()) ; "unopened placeholder")]
+    #[test_case("[[{venv}]]" => Err("`[` cannot be nested".to_string()) ; "nested section")]
+    #[test_case("[{venv}" => Err("`[` without a `]`".to_string()) ; "unclosed section")]
+    #[test_case("{venv}]" => Err("`]` without a `[`".to_string()) ; "unopened section")]
+    #[test_case("\\" => Err("`\\` at the end".to_string()) ; "trailing backslash")]
+    fn render_template_tests(template: &str) -> Result<String, String> {
+        render_template(template, |placeholder| match placeholder {
+            "version" => Ok("3.12".to_string()),
+            "major" => Ok("3".to_string()),
+            "minor" => Ok("12".to_string()),
+            "venv" => Ok(".venv".to_string()),
+            "empty" => Ok(String::new()),
+            _ => Err(format!("unknown placeholder `{{{placeholder}}}`")),
+        })
+    }
+
     #[test]
     fn test_help_message() {
         let launcher_path = "/some/path/to/launcher";
@@ -367,10 +1729,10 @@ mod tests {
     fn test_list_executables() {
         let mut executables: HashMap<ExactVersion, PathBuf> = HashMap::new();
 
-        assert_eq!(
-            list_executables(&executables),
-            Err(crate::Error::NoExecutableFound(RequestedVersion::Any))
-        );
+        assert!(matches!(
+            list_executables(&executables, None, &Config::default()),
+            Err(crate::Error::NoExecutableFound(RequestedVersion::Any, _))
+        ));
 
         let python27_path = "/path/to/2/7/python";
         executables.insert(
@@ -391,7 +1753,7 @@ mod tests {
         // Tests try not to make any guarantees about explicit formatting, just
         // that the interpreters are in descending order of version and the
         // interpreter version comes before the path (i.e. in column order).
-        let executables_list = list_executables(&executables).unwrap();
+        let executables_list = list_executables(&executables, None, &Config::default()).unwrap();
         // No critical data is missing.
         assert!(executables_list.contains("2.7"));
         assert!(executables_list.contains(python27_path));
@@ -413,6 +1775,85 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_list_executables_prereleases() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let python314 = temp_dir.path().join("python3.14");
+        fs::write(&python314, "#!/bin/sh\necho beta\n").unwrap();
+        fs::set_permissions(&python314, fs::Permissions::from_mode(0o755)).unwrap();
+        let executables = HashMap::from([
+            (
+                ExactVersion {
+                    major: 3,
+                    minor: 14,
+                },
+                python314,
+            ),
+            (
+                ExactVersion {
+                    major: 3,
+                    minor: 13,
+                },
+                PathBuf::from("/path/to/python3.13"),
+            ),
+        ]);
+
+        let config = Config {
+            prereleases: vec![ExactVersion {
+                major: 3,
+                minor: 13,
+            }],
+            ..Config::default()
+        };
+        let executables_list = list_executables(&executables, None, &config).unwrap();
+        assert!(executables_list.contains("3.13 (pre-release)"));
+        assert!(!executables_list.contains("3.14 (pre-release)"));
+
+        let config = Config {
+            probe_prereleases: true,
+            ..Config::default()
+        };
+        let executables_list = list_executables(&executables, None, &config).unwrap();
+        assert!(executables_list.contains("3.14 (pre-release)"));
+        assert!(!executables_list.contains("3.13 (pre-release)"));
+    }
+
+    #[test]
+    fn test_list_executables_with_venv() {
+        let mut executables: HashMap<ExactVersion, PathBuf> = HashMap::new();
+        let venv = VirtualEnv {
+            root: PathBuf::from("/path/to/project/.venv"),
+            home: Some(PathBuf::from("/path/to/3/6")),
+            version: Some(ExactVersion { major: 3, minor: 6 }),
+            executable: None,
+            prompt: None,
+        };
+
+        // A virtual environment on its own is enough to list something.
+        let venv_list = list_executables(&executables, Some(&venv), &Config::default()).unwrap();
+        assert!(venv_list.contains("3.6"));
+        assert!(venv_list.contains("/path/to/project/.venv/bin/python"));
+        assert!(venv_list.contains("default"));
+        assert!(venv_list.contains("/path/to/3/6/python3.6"));
+
+        let python37_path = "/path/to/3/7/python";
+        executables.insert(
+            ExactVersion { major: 3, minor: 7 },
+            PathBuf::from(python37_path),
+        );
+
+        // The virtual environment comes first even if it isn't the newest.
+        let executables_list =
+            list_executables(&executables, Some(&venv), &Config::default()).unwrap();
+        assert!(
+            executables_list
+                .find("/path/to/project/.venv/bin/python")
+                .unwrap()
+                < executables_list.find(python37_path).unwrap()
+        );
+        assert_eq!(executables_list.matches("default").count(), 1);
+    }
+
     #[test]
     fn test_venv_executable_path() {
         let venv_root = "/path/to/venv";
@@ -422,6 +1863,130 @@ mod tests {
         );
     }
 
+    fn boundaries_for(start: &Path) -> SearchBoundaries {
+        SearchBoundaries {
+            start: start.to_path_buf(),
+            home: None,
+            ceiling_dirs: Vec::new(),
+            device: None,
+        }
+    }
+
+    #[test]
+    fn search_boundaries_filesystem_root() {
+        let boundaries = boundaries_for(Path::new("/a/b"));
+
+        assert_eq!(
+            boundaries.ancestors().collect::<Vec<_>>(),
+            [Path::new("/a/b"), Path::new("/a"), Path::new("/")]
+        );
+    }
+
+    #[test]
+    fn search_boundaries_home() {
+        let mut boundaries = boundaries_for(Path::new("/home/user/project"));
+        boundaries.home = Some(PathBuf::from("/home/user"));
+
+        assert_eq!(
+            boundaries.ancestors().collect::<Vec<_>>(),
+            [Path::new("/home/user/project")]
+        );
+
+        // Starting in the home directory searches it.
+        boundaries.start = PathBuf::from("/home/user");
+        assert_eq!(
+            boundaries.ancestors().collect::<Vec<_>>(),
+            [Path::new("/home/user"), Path::new("/home"), Path::new("/")]
+        );
+    }
+
+    #[test]
+    fn search_boundaries_ceiling_dirs() {
+        let mut boundaries = boundaries_for(Path::new("/srv/shared/project/src"));
+        boundaries.ceiling_dirs = vec![PathBuf::from("/srv/shared")];
+
+        assert_eq!(
+            boundaries.ancestors().collect::<Vec<_>>(),
+            [
+                Path::new("/srv/shared/project/src"),
+                Path::new("/srv/shared/project")
+            ]
+        );
+    }
+
+    #[test]
+    fn search_boundaries_repository_root() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = temp_dir.path().join("repo");
+        let subdir = repo.join("subdir");
+        fs::create_dir_all(&subdir).unwrap();
+        fs::create_dir(repo.join(".git")).unwrap();
+        let boundaries = boundaries_for(&subdir);
+
+        assert_eq!(
+            boundaries.ancestors().collect::<Vec<_>>(),
+            [subdir.as_path(), repo.as_path()]
+        );
+    }
+
+    #[test_case(1000, 1000 => true ; "owned by the current user")]
+    #[test_case(0, 1000 => true ; "owned by root")]
+    #[test_case(1001, 1000 => false ; "owned by another user")]
+    #[test_case(1000, 0 => false ; "owned by another user when root")]
+    fn check_owner_tests(owner: u32, euid: u32) -> bool {
+        check_owner(Path::new("/path/to/.venv"), owner, euid).is_ok()
+    }
+
+    #[test_case(0o40755 => true ; "typical directory")]
+    #[test_case(0o40775 => true ; "group-writable")]
+    #[test_case(0o40777 => false ; "world-writable")]
+    #[test_case(0o41777 => true ; "world-writable with the sticky bit")]
+    fn check_permissions_tests(mode: u32) -> bool {
+        check_permissions(Path::new("/path/to"), mode).is_ok()
+    }
+
+    fn to_args(args: &str) -> Vec<OsString> {
+        args.split_whitespace().map(OsString::from).collect()
+    }
+
+    #[test_case("" => PythonTarget::Stdin ; "no arguments")]
+    #[test_case("script.py" => PythonTarget::Script(PathBuf::from("script.py")) ; "script")]
+    #[test_case("script.py -u" => PythonTarget::Script(PathBuf::from("script.py")) ; "arguments to the script")]
+    #[test_case("-u script.py" => PythonTarget::Script(PathBuf::from("script.py")) ; "flag")]
+    #[test_case("-uB -E script.py" => PythonTarget::Script(PathBuf::from("script.py")) ; "multiple flags")]
+    #[test_case("-X dev script.py" => PythonTarget::Script(PathBuf::from("script.py")) ; "option with a separate argument")]
+    #[test_case("-Xdev script.py" => PythonTarget::Script(PathBuf::from("script.py")) ; "option with an attached argument")]
+    #[test_case("-uW error script.py" => PythonTarget::Script(PathBuf::from("script.py")) ; "combined flags ending with an option")]
+    #[test_case("-uWerror script.py" => PythonTarget::Script(PathBuf::from("script.py")) ; "combined flags with an attached argument")]
+    #[test_case("--check-hash-based-pycs never script.py" => PythonTarget::Script(PathBuf::from("script.py")) ; "long option with an argument")]
+    #[test_case("-- -script.py" => PythonTarget::Script(PathBuf::from("-script.py")) ; "end of options")]
+    #[test_case("-m pip install" => PythonTarget::Module("pip".to_string()) ; "module")]
+    #[test_case("-I -mpip" => PythonTarget::Module("pip".to_string()) ; "attached module")]
+    #[test_case("-um pip" => PythonTarget::Module("pip".to_string()) ; "module combined with flags")]
+    #[test_case("-m" => PythonTarget::Stdin ; "missing module")]
+    #[test_case("-c pass script.py" => PythonTarget::Command ; "command")]
+    #[test_case("-ic pass" => PythonTarget::Command ; "command combined with flags")]
+    #[test_case("- script.py" => PythonTarget::Stdin ; "stdin")]
+    #[test_case("-i" => PythonTarget::Stdin ; "REPL")]
+    #[test_case("-Z script.py" => PythonTarget::Stdin ; "unrecognized short option")]
+    #[test_case("--unknown script.py" => PythonTarget::Stdin ; "unrecognized long option")]
+    fn python_target_tests(args: &str) -> PythonTarget {
+        PythonTarget::from_args(&to_args(args))
+    }
+
+    #[test]
+    fn python_target_non_utf8() {
+        let script = OsString::from_vec(b"caf\xe9.py".to_vec());
+        assert_eq!(
+            PythonTarget::from_args(&[OsString::from("-u"), script.clone()]),
+            PythonTarget::Script(PathBuf::from(script))
+        );
+        assert_eq!(
+            PythonTarget::from_args(&[OsString::from_vec(b"-mcaf\xe9".to_vec())]),
+            PythonTarget::Module("caf\u{FFFD}".to_string())
+        );
+    }
+
     #[test_case("/usr/bin/python" => None ; "missing shebang comment")]
     #[test_case("# /usr/bin/python" => None ; "missing exclamation point")]
     #[test_case("! /usr/bin/python" => None ; "missing octothorpe")]
@@ -434,14 +1999,95 @@ mod tests {
     #[test_case("#! /usr/bin/python3.7" => Some(RequestedVersion::Exact(3, 7)) ; "typical 'python' with minor version")]
     #[test_case("#! python3.7" => Some(RequestedVersion::Exact(3, 7)) ; "bare 'python' with minor version")]
     #[test_case("#!/usr/bin/python" => Some(RequestedVersion::Any) ; "no space between shebang and path")]
+    #[test_case("#! /usr/bin/env python3" => Some(RequestedVersion::MajorOnly(3)) ; "typical 'env python' with major version")]
+    #[test_case("#!/opt/python/bin/python3.11" => Some(RequestedVersion::Exact(3, 11)) ; "arbitrary absolute path")]
+    #[test_case("#!/bin/env python3.7" => Some(RequestedVersion::Exact(3, 7)) ; "'env' in a different directory")]
+    #[test_case("#!/usr/bin/env -S python3.7 -u" => Some(RequestedVersion::Exact(3, 7)) ; "env -S")]
+    #[test_case("#!/usr/bin/env PYTHONUTF8=1 python3.7" => Some(RequestedVersion::Exact(3, 7)) ; "env with a variable assignment")]
+    #[test_case("#!/usr/bin/env ruby" => None ; "non-Python 'env'")]
+    #[test_case("#!/usr/bin/python3-config" => None ; "not a Python interpreter")]
+    #[test_case("#!" => None ; "empty shebang")]
     fn parse_python_shebang_tests(shebang: &str) -> Option<RequestedVersion> {
+        parse_python_shebang(&mut shebang.as_bytes()).map(|shebang| shebang.requested_version)
+    }
+
+    #[test_case("#! /usr/bin/python" => Some(vec![]) ; "no arguments")]
+    #[test_case("#! /usr/bin/python -u" => Some(vec!["-u".to_string()]) ; "single argument")]
+    #[test_case("#!/usr/bin/env python3 -X dev" => Some(vec!["-X".to_string(), "dev".to_string()]) ; "env with multiple arguments")]
+    #[test_case("#!/usr/bin/env -S python3 -u -W error" => Some(vec!["-u".to_string(), "-W".to_string(), "error".to_string()]) ; "env -S with arguments")]
+    #[test_case("#!/usr/bin/env -Spython3 -u" => Some(vec!["-u".to_string()]) ; "env -S with the command attached")]
+    #[test_case("#!/usr/bin/env --split-string=python3 -u" => Some(vec!["-u".to_string()]) ; "env --split-string=")]
+    #[test_case("#!/usr/bin/env -S python3 -W 'ignore:some warning'" => Some(vec!["-W".to_string(), "ignore:some warning".to_string()]) ; "env -S with a quoted argument")]
+    #[test_case("#! /bin/sh -e" => None ; "non-Python shebang with arguments")]
+    #[test_case("#!/usr/bin/python -u\0x" => None ; "NUL byte in an argument")]
+    fn parse_python_shebang_args_tests(shebang: &str) -> Option<Vec<String>> {
+        parse_python_shebang(&mut shebang.as_bytes()).map(|shebang| shebang.args)
+    }
+
+    #[test_case("#!/usr/bin/env python3.7" => Some(PathBuf::from("python3.7")) ; "env")]
+    #[test_case("#!/opt/python/bin/python3.11 -u" => Some(PathBuf::from("/opt/python/bin/python3.11")) ; "absolute path")]
+    fn parse_python_shebang_interpreter_tests(shebang: &str) -> Option<PathBuf> {
+        parse_python_shebang(&mut shebang.as_bytes()).map(|shebang| shebang.interpreter)
+    }
+
+    #[test_case("#!/srv/app/.venv/bin/python" => Some(PathBuf::from("/srv/app/.venv/bin/python")) ; "virtual environment")]
+    #[test_case("#!/opt/python/bin/python3.11 -u" => Some(PathBuf::from("/opt/python/bin/python3.11")) ; "absolute path")]
+    #[test_case("#!/usr/bin/python3" => None ; "/usr/bin")]
+    #[test_case("#!/usr/local/bin/python3.11" => None ; "/usr/local/bin")]
+    #[test_case("#!/usr/bin/env python3" => None ; "env")]
+    #[test_case("#!python3" => None ; "relative path")]
+    fn shebang_absolute_interpreter_tests(shebang: &str) -> Option<PathBuf> {
         parse_python_shebang(&mut shebang.as_bytes())
+            .and_then(|shebang| shebang.absolute_interpreter().map(Path::to_path_buf))
+    }
+
+    #[test_case("" => Vec::<String>::new() ; "empty")]
+    #[test_case("  -u   -B " => vec!["-u".to_string(), "-B".to_string()] ; "extra whitespace")]
+    #[test_case(r#"-c "print('hi')""# => vec!["-c".to_string(), "print('hi')".to_string()] ; "double quotes")]
+    #[test_case(r#"-c 'a "b" c'"# => vec!["-c".to_string(), r#"a "b" c"#.to_string()] ; "single quotes")]
+    #[test_case(r#"a\ b"# => vec!["a b".to_string()] ; "escaped space")]
+    #[test_case(r#"''"# => vec!["".to_string()] ; "empty quotes")]
+    fn split_args_tests(args: &str) -> Vec<String> {
+        split_args(args)
+    }
+
+    #[test_case("#!/usr/bin/python3.7\r\nprint('Hello')\r\n" => Some(RequestedVersion::Exact(3, 7)) ; "CRLF")]
+    #[test_case("#!/usr/bin/python3.7\nprint('Hello')\n" => Some(RequestedVersion::Exact(3, 7)) ; "LF")]
+    #[test_case("\u{feff}#!/usr/bin/python3.7\r\n" => Some(RequestedVersion::Exact(3, 7)) ; "BOM")]
+    #[test_case("\u{feff}\u{feff}#!/usr/bin/python3.7\n" => None ; "multiple BOMs")]
+    #[test_case("\n#!/usr/bin/python3.7\n" => None ; "shebang not on the first line")]
+    fn parse_python_shebang_line_ending_tests(contents: &str) -> Option<RequestedVersion> {
+        parse_python_shebang(&mut contents.as_bytes()).map(|shebang| shebang.requested_version)
+    }
+
+    #[test]
+    fn parse_python_shebang_crlf_args() {
+        let contents = "#!/usr/bin/env python3 -u\r\n";
+        let shebang = parse_python_shebang(&mut contents.as_bytes()).unwrap();
+
+        assert_eq!(shebang.args, ["-u".to_string()]);
+    }
+
+    #[test]
+    fn parse_python_shebang_long_line() {
+        let padding = " ".repeat(MAX_SHEBANG_LENGTH as usize);
+        let just_fits = format!("#!/usr/bin/python3.7{}\n", &padding[..padding.len() - 21]);
+        assert_eq!(just_fits.len() as u64, MAX_SHEBANG_LENGTH);
+        assert_eq!(
+            parse_python_shebang(&mut just_fits.as_bytes())
+                .map(|shebang| shebang.requested_version),
+            Some(RequestedVersion::Exact(3, 7))
+        );
+
+        let too_long = format!("#!/usr/bin/python3.7{padding}\n");
+        assert_eq!(parse_python_shebang(&mut too_long.as_bytes()), None);
     }
 
     #[test_case(&[0x23, 0x21, 0xc0, 0xaf] => None ; "invalid UTF-8")]
+    #[test_case(&[0xef, 0xbb, 0xbf, 0x23, 0x21, 0xc0, 0xaf] => None ; "BOM with invalid UTF-8")]
     fn parse_python_sheban_include_invalid_bytes_tests(
         mut shebang: &[u8],
     ) -> Option<RequestedVersion> {
-        parse_python_shebang(&mut shebang)
+        parse_python_shebang(&mut shebang).map(|shebang| shebang.requested_version)
     }
 }
@@ -0,0 +1,464 @@
+//! Generating shell completions
+//!
+//! The completion scripts are generated by the Launcher itself so that the
+//! version flags (e.g. `-3.12` and `-3`) reflect the interpreters found via
+//! [`crate::all_executables`] when the script is generated, e.g. whenever a
+//! shell starts and runs `py --completions bash`.
+
+use std::{collections::HashMap, fmt::Write, path::PathBuf, str::FromStr};
+
+use crate::{ComponentSize, ExactVersion};
+
+/// The shells which completions can be generated for.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Shell {
+    /// [Bash](https://www.gnu.org/software/bash/).
+    Bash,
+    /// [Zsh](https://www.zsh.org/).
+    Zsh,
+    /// [fish](https://fishshell.com/).
+    Fish,
+    /// [Nushell](https://www.nushell.sh/).
+    Nushell,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(shell: &str) -> Result<Self, Self::Err> {
+        match shell {
+            "bash" => Ok(Self::Bash),
+            "zsh" => Ok(Self::Zsh),
+            "fish" => Ok(Self::Fish),
+            "nushell" | "nu" => Ok(Self::Nushell),
+            _ => Err(format!("unsupported shell '{shell}'")),
+        }
+    }
+}
+
+impl Shell {
+    /// Quotes `word` so the shell treats it as a single, literal argument.
+    pub fn quote(self, word: &str) -> String {
+        match self {
+            Self::Bash | Self::Zsh => {
+                let escaped = word.replace('\'', r"'\''");
+                format!("'{escaped}'")
+            }
+            Self::Fish => {
+                let escaped = word.replace('\\', r"\\").replace('\'', r"\'");
+                format!("'{escaped}'")
+            }
+            Self::Nushell => {
+                let escaped = word.replace('\\', r"\\").replace('"', "\\\"");
+                format!("\"{escaped}\"")
+            }
+        }
+    }
+}
+
+/// The names of the shells accepted by `--completions`.
+static SHELLS: [&str; 4] = ["bash", "zsh", "fish", "nushell"];
+
+/// An argument for the Launcher itself.
+struct LauncherArg {
+    /// The flag, e.g. `--list`.
+    flag: &'static str,
+    /// The values the flag accepts.
+    values: &'static [&'static str],
+    /// Whether a value is attached to the flag with `=` instead of being the
+    /// following argument.
+    attached: bool,
+    description: &'static str,
+}
+
+impl LauncherArg {
+    const fn new(flag: &'static str, description: &'static str) -> Self {
+        Self {
+            flag,
+            values: &[],
+            attached: false,
+            description,
+        }
+    }
+}
+
+static LAUNCHER_ARGS: [LauncherArg; 12] = [
+    LauncherArg::new("-h", "Print a help message and exit"),
+    LauncherArg::new("--help", "Print a help message and exit"),
+    LauncherArg::new("--list", "List all known interpreters"),
+    LauncherArg::new(
+        "--launcher-version",
+        "Print the version of the Launcher and its default interpreter",
+    ),
+    LauncherArg::new(
+        "--doctor",
+        "Diagnose why an unexpected interpreter may be chosen",
+    ),
+    LauncherArg::new(
+        "--explain",
+        "Explain which interpreter would be launched and why",
+    ),
+    LauncherArg::new(
+        "--prompt-info",
+        "Print the default interpreter's version for a shell prompt",
+    ),
+    LauncherArg::new("--venv", "Create a virtual environment"),
+    LauncherArg::new(
+        "--refresh-venv",
+        "Recreate an out-of-date virtual environment",
+    ),
+    LauncherArg {
+        flag: "--completions",
+        values: &SHELLS,
+        attached: false,
+        description: "Print the completion script for a shell",
+    },
+    LauncherArg {
+        flag: "--shell-init",
+        values: &["bash", "zsh", "fish"],
+        attached: false,
+        description: "Print the hook which activates virtual environments",
+    },
+    LauncherArg {
+        flag: "--error-format",
+        values: &["text", "json"],
+        attached: true,
+        description: "Report errors from the Launcher in a format",
+    },
+];
+
+/// The version flags for `executables` along with what they launch, newest
+/// version first.
+///
+/// Every `-X.Y` flag is included, along with a `-X` flag for the newest
+/// version of every major version.
+fn version_flags(executables: &HashMap<ExactVersion, PathBuf>) -> Vec<(String, String)> {
+    let mut executable_pairs = Vec::from_iter(executables);
+    executable_pairs.sort_unstable();
+    executable_pairs.reverse();
+
+    let mut flags = Vec::new();
+    let mut seen_major_versions: Vec<ComponentSize> = Vec::new();
+    for (version, path) in executable_pairs {
+        let printable_path = path.display();
+        flags.push((format!("-{version}"), format!("Launch {printable_path}")));
+        if !seen_major_versions.contains(&version.major) {
+            seen_major_versions.push(version.major);
+            let major = version.major;
+            flags.push((format!("-{major}"), format!("Launch {printable_path}")));
+        }
+    }
+
+    flags
+}
+
+/// Every argument which can be the first one passed to the Launcher along
+/// with its description.
+fn first_args(executables: &HashMap<ExactVersion, PathBuf>) -> Vec<(String, String)> {
+    let mut args = Vec::new();
+    for arg in &LAUNCHER_ARGS {
+        if arg.attached {
+            for value in arg.values {
+                let flag = arg.flag;
+                args.push((format!("{flag}={value}"), arg.description.to_string()));
+            }
+        } else {
+            args.push((arg.flag.to_string(), arg.description.to_string()));
+        }
+    }
+    args.extend(version_flags(executables));
+    args
+}
+
+/// The arguments whose value is the following argument.
+fn args_with_separate_values() -> impl Iterator<Item = &'static LauncherArg> {
+    LAUNCHER_ARGS
+        .iter()
+        .filter(|arg| !arg.attached && !arg.values.is_empty())
+}
+
+/// Generates the completion script for `shell`.
+///
+/// The version flags are based on `executables` (typically the result of
+/// [`crate::all_executables`]).
+///
+/// # Panics
+///
+/// If a [`writeln!`] call fails.
+pub fn script(shell: Shell, executables: &HashMap<ExactVersion, PathBuf>) -> String {
+    match shell {
+        Shell::Bash => bash_script(executables),
+        Shell::Zsh => zsh_script(executables),
+        Shell::Fish => fish_script(executables),
+        Shell::Nushell => nushell_script(executables),
+    }
+}
+
+fn bash_script(executables: &HashMap<ExactVersion, PathBuf>) -> String {
+    let words = first_args(executables)
+        .into_iter()
+        .map(|(word, _)| word)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut script = String::new();
+    writeln!(script, "# Generated by `py --completions bash`.").unwrap();
+    writeln!(script, "_py() {{").unwrap();
+    writeln!(script, "    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"").unwrap();
+    writeln!(script, "    if [[ $COMP_CWORD -eq 1 && $cur == -* ]]; then").unwrap();
+    writeln!(
+        script,
+        "        COMPREPLY=($(compgen -W {} -- \"$cur\"))",
+        Shell::Bash.quote(&words)
+    )
+    .unwrap();
+    for arg in args_with_separate_values() {
+        writeln!(
+            script,
+            "    elif [[ $COMP_CWORD -eq 2 && ${{COMP_WORDS[1]}} == {} ]]; then",
+            arg.flag
+        )
+        .unwrap();
+        writeln!(
+            script,
+            "        COMPREPLY=($(compgen -W {} -- \"$cur\"))",
+            Shell::Bash.quote(&arg.values.join(" "))
+        )
+        .unwrap();
+    }
+    writeln!(script, "    fi").unwrap();
+    writeln!(script, "}}").unwrap();
+    writeln!(script, "complete -o default -F _py py").unwrap();
+    script
+}
+
+fn zsh_script(executables: &HashMap<ExactVersion, PathBuf>) -> String {
+    let mut script = String::new();
+    writeln!(script, "#compdef py").unwrap();
+    writeln!(script, "# Generated by `py --completions zsh`.").unwrap();
+    writeln!(script, "_py() {{").unwrap();
+    writeln!(
+        script,
+        "    if (( CURRENT == 2 )) && [[ $PREFIX == -* ]]; then"
+    )
+    .unwrap();
+    writeln!(script, "        local -a launcher_args").unwrap();
+    writeln!(script, "        launcher_args=(").unwrap();
+    for (word, description) in first_args(executables) {
+        // `_describe` splits on the first unescaped colon.
+        let entry = format!("{}:{description}", word.replace(':', r"\:"));
+        writeln!(script, "            {}", Shell::Zsh.quote(&entry)).unwrap();
+    }
+    writeln!(script, "        )").unwrap();
+    writeln!(
+        script,
+        "        _describe -t launcher-args 'launcher argument' launcher_args"
+    )
+    .unwrap();
+    for arg in args_with_separate_values() {
+        writeln!(
+            script,
+            "    elif (( CURRENT == 3 )) && [[ ${{words[2]}} == {} ]]; then",
+            arg.flag
+        )
+        .unwrap();
+        writeln!(script, "        _values value {}", arg.values.join(" ")).unwrap();
+    }
+    writeln!(script, "    else").unwrap();
+    writeln!(script, "        _files").unwrap();
+    writeln!(script, "    fi").unwrap();
+    writeln!(script, "}}").unwrap();
+    writeln!(script, "compdef _py py").unwrap();
+    script
+}
+
+fn fish_script(executables: &HashMap<ExactVersion, PathBuf>) -> String {
+    let mut script = String::new();
+    writeln!(script, "# Generated by `py --completions fish`.").unwrap();
+    // While wrapping the latest Python version specifically would provide the
+    // most accurate completions, fish only ships with completions for
+    // `python` itself.
+    writeln!(script, "complete -c py --wraps python").unwrap();
+
+    for arg in &LAUNCHER_ARGS {
+        let option = match arg.flag.strip_prefix("--") {
+            Some(long) => format!("--long-option {long}"),
+            None => format!("--short-option {}", &arg.flag[1..]),
+        };
+        let values = if arg.values.is_empty() {
+            String::new()
+        } else {
+            format!(
+                " --require-parameter --arguments {}",
+                Shell::Fish.quote(&arg.values.join(" "))
+            )
+        };
+        writeln!(
+            script,
+            "complete -c py {option} --no-files{values} -d {}",
+            Shell::Fish.quote(arg.description)
+        )
+        .unwrap();
+    }
+
+    for (flag, description) in version_flags(executables) {
+        // Must use `--old-option` as a version is not a single character.
+        writeln!(
+            script,
+            "complete -c py --old-option {} -d {}",
+            &flag[1..],
+            Shell::Fish.quote(&description)
+        )
+        .unwrap();
+    }
+
+    script
+}
+
+fn nushell_script(executables: &HashMap<ExactVersion, PathBuf>) -> String {
+    let mut script = String::new();
+    writeln!(script, "# Generated by `py --completions nushell`.").unwrap();
+    writeln!(script, "def \"nu-complete py\" [context: string] {{").unwrap();
+    writeln!(script, "    let words = ($context | split row ' ')").unwrap();
+    for arg in args_with_separate_values() {
+        writeln!(
+            script,
+            "    if ($words | length) > 2 and ($words | reverse | get 1) == {} {{",
+            Shell::Nushell.quote(arg.flag)
+        )
+        .unwrap();
+        let values = arg
+            .values
+            .iter()
+            .map(|value| Shell::Nushell.quote(value))
+            .collect::<Vec<_>>()
+            .join(" ");
+        writeln!(script, "        return [{values}]").unwrap();
+        writeln!(script, "    }}").unwrap();
+    }
+    // Only the first argument is for the launcher; returning `null` for the
+    // rest falls back to Nushell's file completion.
+    writeln!(script, "    if ($words | length) > 2 {{").unwrap();
+    writeln!(script, "        return null").unwrap();
+    writeln!(script, "    }}").unwrap();
+    writeln!(script, "    [").unwrap();
+    for (word, description) in first_args(executables) {
+        writeln!(
+            script,
+            "        {{ value: {}, description: {} }}",
+            Shell::Nushell.quote(&word),
+            Shell::Nushell.quote(&description)
+        )
+        .unwrap();
+    }
+    writeln!(script, "    ]").unwrap();
+    writeln!(script, "}}").unwrap();
+    writeln!(script).unwrap();
+    writeln!(script, "export extern \"py\" [").unwrap();
+    writeln!(script, "    ...args: string@\"nu-complete py\"").unwrap();
+    writeln!(script, "]").unwrap();
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn executables() -> HashMap<ExactVersion, PathBuf> {
+        HashMap::from([
+            (
+                ExactVersion::new(3, 11),
+                PathBuf::from("/usr/bin/python3.11"),
+            ),
+            (
+                ExactVersion::new(3, 12),
+                PathBuf::from("/usr/local/bin/python3.12"),
+            ),
+            (ExactVersion::new(2, 7), PathBuf::from("/usr/bin/python2.7")),
+        ])
+    }
+
+    #[test_case("bash" => Ok(Shell::Bash) ; "bash")]
+    #[test_case("zsh" => Ok(Shell::Zsh) ; "zsh")]
+    #[test_case("fish" => Ok(Shell::Fish) ; "fish")]
+    #[test_case("nushell" => Ok(Shell::Nushell) ; "nushell")]
+    #[test_case("nu" => Ok(Shell::Nushell) ; "nu")]
+    #[test_case("csh" => Err("unsupported shell 'csh'".to_string()) ; "unsupported")]
+    fn shell_from_str_tests(shell: &str) -> Result<Shell, String> {
+        Shell::from_str(shell)
+    }
+
+    #[test]
+    fn test_version_flags() {
+        assert_eq!(
+            version_flags(&executables()),
+            [
+                ("-3.12", "Launch /usr/local/bin/python3.12"),
+                ("-3", "Launch /usr/local/bin/python3.12"),
+                ("-3.11", "Launch /usr/bin/python3.11"),
+                ("-2.7", "Launch /usr/bin/python2.7"),
+                ("-2", "Launch /usr/bin/python2.7"),
+            ]
+            .map(|(flag, description)| (flag.to_string(), description.to_string()))
+        );
+    }
+
+    #[test_case(Shell::Bash, "it's" => "'it'\\''s'" ; "bash single quote")]
+    #[test_case(Shell::Zsh, "plain" => "'plain'" ; "zsh plain")]
+    #[test_case(Shell::Fish, r"it's \o/" => r"'it\'s \\o/'" ; "fish escapes")]
+    #[test_case(Shell::Nushell, r#"say "hi" \o/"# => r#""say \"hi\" \\o/""# ; "nushell escapes")]
+    fn shell_quote_tests(shell: Shell, word: &str) -> String {
+        shell.quote(word)
+    }
+
+    #[test]
+    fn bash_completions() {
+        let script = script(Shell::Bash, &executables());
+        assert!(script.contains("compgen -W '-h --help --list "));
+        assert!(script.contains(" --error-format=text --error-format=json -3.12 -3 -3.11 -2.7 -2'"));
+        assert!(script.contains("compgen -W 'bash zsh fish nushell'"));
+        assert!(script.contains("${COMP_WORDS[1]} == --shell-init ]]; then\n        COMPREPLY=($(compgen -W 'bash zsh fish' -- \"$cur\"))"));
+        assert!(script.ends_with("complete -o default -F _py py\n"));
+    }
+
+    #[test]
+    fn zsh_completions() {
+        let script = script(Shell::Zsh, &executables());
+        assert!(script.starts_with("#compdef py\n"));
+        assert!(script.contains("            '-3.12:Launch /usr/local/bin/python3.12'\n"));
+        assert!(script.contains("            '--list:List all known interpreters'\n"));
+        assert!(script.contains("_values value bash zsh fish nushell\n"));
+        assert!(script.contains(
+            "[[ ${words[2]} == --shell-init ]]; then\n        _values value bash zsh fish\n"
+        ));
+    }
+
+    #[test]
+    fn fish_completions() {
+        let script = script(Shell::Fish, &executables());
+        assert!(script.contains(
+            "complete -c py --short-option h --no-files -d 'Print a help message and exit'\n"
+        ));
+        assert!(script.contains("complete -c py --long-option completions --no-files --require-parameter --arguments 'bash zsh fish nushell' -d "));
+        assert!(script
+            .contains("complete -c py --old-option 3 -d 'Launch /usr/local/bin/python3.12'\n"));
+        assert!(script.contains("complete -c py --old-option 2.7 -d 'Launch /usr/bin/python2.7'\n"));
+    }
+
+    #[test]
+    fn nushell_completions() {
+        let script = script(Shell::Nushell, &executables());
+        assert!(script.contains(
+            "        { value: \"-3.11\", description: \"Launch /usr/bin/python3.11\" }\n"
+        ));
+        assert!(script.contains("return [\"bash\" \"zsh\" \"fish\" \"nushell\"]"));
+        assert!(script
+            .contains("get 1) == \"--shell-init\" {\n        return [\"bash\" \"zsh\" \"fish\"]"));
+        assert!(
+            script.contains("    if ($words | length) > 2 {\n        return null\n    }\n    [\n")
+        );
+        assert!(script.contains("...args: string@\"nu-complete py\""));
+    }
+}
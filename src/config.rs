@@ -0,0 +1,449 @@
+//! Configuration of the Launcher
+//!
+//! The [`Config`] struct holds the settings which can be specified via a
+//! configuration file and environment variables. The configuration file uses
+//! an INI format similar to the `py.ini` file of the
+//! [Python Launcher for Windows]:
+//!
+//! ```ini
+//! [venv]
+//! dirs = .venv, venv
+//!
+//! [search]
+//! path = /opt/python/*/bin
+//! ```
+//!
+//! Environment variables take precedence over the configuration file.
+//!
+//! [Python Launcher for Windows]: https://docs.python.org/3/using/windows.html#customization-via-ini-files
+
+use std::{
+    env, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{cli::DEFAULT_VENV_DIR, ExactVersion};
+
+/// The environment variable specifying the path to the configuration file.
+pub static CONFIG_ENV_VAR: &str = "PY_LAUNCHER_CONFIG";
+
+/// Every setting as `(section, key, environment variable)`.
+const SETTINGS: [(&str, &str, &str); 13] = [
+    ("venv", "dirs", "PY_LAUNCHER_VENV_DIRS"),
+    ("venv", "search", "PY_LAUNCHER_VENV_SEARCH"),
+    ("venv", "ceiling_dirs", "PY_LAUNCHER_VENV_CEILING_DIRS"),
+    ("venv", "allow_unsafe", "PY_LAUNCHER_VENV_ALLOW_UNSAFE"),
+    ("search", "path", "PY_LAUNCHER_PATH"),
+    ("search", "position", "PY_LAUNCHER_PATH_POSITION"),
+    ("search", "exclude", "PY_LAUNCHER_EXCLUDE"),
+    ("search", "prereleases", "PY_LAUNCHER_PRERELEASES"),
+    (
+        "search",
+        "probe_prereleases",
+        "PY_LAUNCHER_PROBE_PRERELEASES",
+    ),
+    ("search", "stable", "PY_LAUNCHER_STABLE"),
+    ("search", "prefer_system", "PY_LAUNCHER_PREFER_SYSTEM"),
+    ("launch", "supervise", "PY_LAUNCHER_SUPERVISE"),
+    ("launch", "post_exit", "PY_LAUNCHER_POST_EXIT"),
+];
+
+/// Where the directories of [`Config::search_path`] are searched relative to
+/// `PATH`.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum SearchPosition {
+    /// Search the directories before `PATH`, so their interpreters take
+    /// precedence.
+    #[default]
+    Before,
+    /// Search the directories after `PATH`.
+    After,
+}
+
+impl SearchPosition {
+    fn parse(value: &str) -> std::result::Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "before" => Ok(Self::Before),
+            "after" => Ok(Self::After),
+            _ => Err(format!("'{value}' is not `before` or `after`")),
+        }
+    }
+}
+
+/// Settings for the Launcher.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Config {
+    /// The configuration file the settings were read from (if any).
+    pub path: Option<PathBuf>,
+    /// Directory names to search for a virtual environment in, in order of
+    /// preference (`[venv] dirs` or `PY_LAUNCHER_VENV_DIRS`).
+    pub venv_dirs: Vec<String>,
+    /// Whether to search the current and parent directories for a virtual
+    /// environment (`[venv] search` or `PY_LAUNCHER_VENV_SEARCH`).
+    pub venv_search: bool,
+    /// Directories the search for a virtual environment will not go into
+    /// when coming from a subdirectory, like `GIT_CEILING_DIRECTORIES`
+    /// (`[venv] ceiling_dirs` or `PY_LAUNCHER_VENV_CEILING_DIRS`).
+    pub venv_ceiling_dirs: Vec<PathBuf>,
+    /// Whether to use a virtual environment found via a search even if it is
+    /// owned by another user or is in a directory writable by everyone
+    /// (`[venv] allow_unsafe` or `PY_LAUNCHER_VENV_ALLOW_UNSAFE`).
+    pub venv_allow_unsafe: bool,
+    /// Directories to search for interpreters in addition to `PATH`, which
+    /// may contain `*` and `?` glob patterns (`[search] path` or
+    /// `PY_LAUNCHER_PATH`).
+    pub search_path: Vec<String>,
+    /// Whether [`Self::search_path`] is searched before or after `PATH`
+    /// (`[search] position` or `PY_LAUNCHER_PATH_POSITION`).
+    pub search_position: SearchPosition,
+    /// Executables, directories (both of which may contain `*` and `?` glob
+    /// patterns), and versions (e.g. `3.8`) to ignore when searching for
+    /// interpreters (`[search] exclude` or `PY_LAUNCHER_EXCLUDE`).
+    pub search_exclude: Vec<String>,
+    /// Versions which are pre-releases, and so are skipped unless requested
+    /// exactly or nothing else is available (`[search] prereleases` or
+    /// `PY_LAUNCHER_PRERELEASES`).
+    pub prereleases: Vec<ExactVersion>,
+    /// Whether to run an interpreter to find out if it is a pre-release
+    /// (`[search] probe_prereleases` or `PY_LAUNCHER_PROBE_PRERELEASES`).
+    pub probe_prereleases: bool,
+    /// The version to prefer over newer ones when it satisfies what was
+    /// requested (`[search] stable` or `PY_LAUNCHER_STABLE`).
+    pub stable: Option<ExactVersion>,
+    /// Whether to prefer the interpreter installed by the operating system
+    /// over newer ones (`[search] prefer_system` or
+    /// `PY_LAUNCHER_PREFER_SYSTEM`).
+    pub prefer_system: bool,
+    /// Whether to run the interpreter as a child process instead of replacing
+    /// the Launcher with it (`[launch] supervise` or `PY_LAUNCHER_SUPERVISE`).
+    pub supervise: bool,
+    /// A shell command to run once the interpreter exits, which implies
+    /// [`Self::supervise`] (`[launch] post_exit` or `PY_LAUNCHER_POST_EXIT`).
+    pub post_exit: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            path: None,
+            venv_dirs: vec![DEFAULT_VENV_DIR.to_string()],
+            venv_search: true,
+            venv_ceiling_dirs: Vec::new(),
+            venv_allow_unsafe: false,
+            search_path: Vec::new(),
+            search_position: SearchPosition::default(),
+            search_exclude: Vec::new(),
+            prereleases: Vec::new(),
+            probe_prereleases: false,
+            stable: None,
+            prefer_system: false,
+            supervise: false,
+            post_exit: None,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the configuration file (if it exists) and then applies any
+    /// environment variables.
+    ///
+    /// # Errors
+    ///
+    /// If the configuration file cannot be read, is malformed, or a setting has
+    /// an invalid value, [`crate::Error::InvalidConfig`] is returned. The same
+    /// goes for an environment variable with an invalid value.
+    ///
+    /// It is not an error for the configuration file to not exist unless
+    /// `PY_LAUNCHER_CONFIG` explicitly specifies it.
+    pub fn load() -> crate::Result<Self> {
+        let mut config = match Self::file_path() {
+            Some(path) => {
+                let printable_path = path.display();
+                log::info!("Reading configuration from {printable_path}");
+                match fs::read_to_string(&path) {
+                    Ok(contents) => Self::parse(&path, &contents)?,
+                    Err(error)
+                        if error.kind() == ErrorKind::NotFound
+                            && env::var_os(CONFIG_ENV_VAR).is_none() =>
+                    {
+                        log::debug!("{printable_path} does not exist");
+                        Self::default()
+                    }
+                    Err(error) => {
+                        return Err(crate::Error::InvalidConfig(
+                            printable_path.to_string(),
+                            error.to_string(),
+                        ))
+                    }
+                }
+            }
+            None => Self::default(),
+        };
+
+        config.apply_env()?;
+        log::debug!("Configuration: {config:?}");
+        Ok(config)
+    }
+
+    /// The path to the configuration file.
+    ///
+    /// The `PY_LAUNCHER_CONFIG` environment variable is used if set. Otherwise
+    /// the path is `python-launcher/py.ini` in `$XDG_CONFIG_HOME` (which
+    /// defaults to `~/.config`).
+    pub fn file_path() -> Option<PathBuf> {
+        if let Some(path) = env::var_os(CONFIG_ENV_VAR).filter(|path| !path.is_empty()) {
+            return Some(PathBuf::from(path));
+        }
+
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(config_home.join("python-launcher").join("py.ini"))
+    }
+
+    fn parse(path: &Path, contents: &str) -> crate::Result<Self> {
+        let mut config = Self {
+            path: Some(path.to_path_buf()),
+            ..Self::default()
+        };
+        let printable_path = path.display();
+        let mut section = String::new();
+
+        for (index, raw_line) in contents.lines().enumerate() {
+            let line_number = index + 1;
+            let location = format!("{printable_path}:{line_number}");
+            let line = raw_line.trim();
+
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            } else if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().to_lowercase();
+            } else if let Some((key, value)) = line.split_once('=') {
+                let key = key.trim().to_lowercase();
+                if SETTINGS.iter().any(|(s, k, _)| *s == section && *k == key) {
+                    config
+                        .set(&section, &key, value.trim(), ',')
+                        .map_err(|message| crate::Error::InvalidConfig(location, message))?;
+                } else {
+                    log::warn!("{location}: unknown setting `{key}` in section `[{section}]`");
+                }
+            } else {
+                return Err(crate::Error::InvalidConfig(
+                    location,
+                    "expected `[section]` or `key = value`".to_string(),
+                ));
+            }
+        }
+
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) -> crate::Result<()> {
+        for (section, key, env_var) in SETTINGS {
+            if let Some(value) = env::var_os(env_var).filter(|value| !value.is_empty()) {
+                let value = value.to_string_lossy();
+                log::debug!("{env_var} = '{value}'");
+                self.set(section, key, &value, ':')
+                    .map_err(|message| crate::Error::InvalidConfig(env_var.to_string(), message))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets a setting from its string value.
+    ///
+    /// Lists are separated by `list_separator` as environment variables follow
+    /// the convention of `PATH` while the configuration file uses commas.
+    fn set(
+        &mut self,
+        section: &str,
+        key: &str,
+        value: &str,
+        list_separator: char,
+    ) -> std::result::Result<(), String> {
+        match (section, key) {
+            ("venv", "dirs") => self.venv_dirs = parse_list(value, list_separator),
+            ("venv", "search") => self.venv_search = parse_bool(value)?,
+            ("venv", "ceiling_dirs") => {
+                self.venv_ceiling_dirs = parse_list(value, list_separator)
+                    .into_iter()
+                    .map(PathBuf::from)
+                    .collect()
+            }
+            ("venv", "allow_unsafe") => self.venv_allow_unsafe = parse_bool(value)?,
+            ("search", "path") => self.search_path = parse_list(value, list_separator),
+            ("search", "position") => self.search_position = SearchPosition::parse(value)?,
+            ("search", "exclude") => self.search_exclude = parse_list(value, list_separator),
+            ("search", "prereleases") => {
+                self.prereleases = parse_list(value, list_separator)
+                    .iter()
+                    .map(|version| parse_version(version))
+                    .collect::<std::result::Result<_, _>>()?
+            }
+            ("search", "probe_prereleases") => self.probe_prereleases = parse_bool(value)?,
+            ("search", "stable") => self.stable = Some(parse_version(value)?),
+            ("search", "prefer_system") => self.prefer_system = parse_bool(value)?,
+            ("launch", "supervise") => self.supervise = parse_bool(value)?,
+            ("launch", "post_exit") => self.post_exit = Some(value.to_string()),
+            _ => unreachable!("setting `{key}` in `[{section}]` is not handled"),
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_list(value: &str, separator: char) -> Vec<String> {
+    value
+        .split(separator)
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_bool(value: &str) -> std::result::Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(format!(
+            "'{value}' is not a boolean (e.g. `true` or `false`)"
+        )),
+    }
+}
+
+fn parse_version(value: &str) -> std::result::Result<ExactVersion, String> {
+    ExactVersion::from_str(value)
+        .map_err(|_| format!("'{value}' is not a `major.minor` version (e.g. `3.12`)"))
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(".venv" => vec![".venv".to_string()] ; "single item")]
+    #[test_case(".venv, venv ,env" => vec![".venv".to_string(), "venv".to_string(), "env".to_string()] ; "whitespace is stripped")]
+    #[test_case(".venv,,venv" => vec![".venv".to_string(), "venv".to_string()] ; "empty items are skipped")]
+    #[test_case("" => Vec::<String>::new() ; "empty")]
+    fn parse_list_tests(value: &str) -> Vec<String> {
+        parse_list(value, ',')
+    }
+
+    #[test_case("true" => Ok(true) ; "lowercase true")]
+    #[test_case("On" => Ok(true) ; "case-insensitive")]
+    #[test_case("1" => Ok(true) ; "one")]
+    #[test_case("no" => Ok(false) ; "lowercase no")]
+    #[test_case("0" => Ok(false) ; "zero")]
+    #[test_case("maybe" => matches Err(_) ; "not a boolean")]
+    fn parse_bool_tests(value: &str) -> std::result::Result<bool, String> {
+        parse_bool(value)
+    }
+
+    #[test]
+    fn parse_defaults() {
+        let path = Path::new("/path/to/py.ini");
+        let config = Config::parse(path, "").unwrap();
+
+        assert_eq!(config.path, Some(path.to_path_buf()));
+        assert_eq!(config.venv_dirs, vec![DEFAULT_VENV_DIR.to_string()]);
+        assert!(config.venv_search);
+    }
+
+    #[test]
+    fn parse_settings() {
+        let contents = "# Comment\n\
+                        ; Also a comment\n\
+                        [VENV]\n\
+                        dirs = venv, .venv\n\
+                        Search = false\n\
+                        ceiling_dirs = /srv, /mnt/shared\n\
+                        [search]\n\
+                        path = /opt/python/*/bin, /srv/python/bin\n\
+                        position = After\n\
+                        exclude = /opt/vendor/bin, 2\n\
+                        prereleases = 3.14, 3.15\n\
+                        stable = 3.12\n\
+                        prefer_system = yes\n\
+                        [launch]\n\
+                        post_exit = echo \"exited with $PY_LAUNCHER_EXIT_STATUS\"\n\
+                        [unknown]\n\
+                        key = value\n";
+        let config = Config::parse(Path::new("py.ini"), contents).unwrap();
+
+        assert_eq!(
+            config.venv_dirs,
+            vec!["venv".to_string(), ".venv".to_string()]
+        );
+        assert!(!config.venv_search);
+        assert_eq!(
+            config.venv_ceiling_dirs,
+            vec![PathBuf::from("/srv"), PathBuf::from("/mnt/shared")]
+        );
+        assert_eq!(
+            config.search_path,
+            vec![
+                "/opt/python/*/bin".to_string(),
+                "/srv/python/bin".to_string()
+            ]
+        );
+        assert_eq!(config.search_position, SearchPosition::After);
+        assert_eq!(
+            config.search_exclude,
+            vec!["/opt/vendor/bin".to_string(), "2".to_string()]
+        );
+        assert_eq!(
+            config.prereleases,
+            vec![
+                ExactVersion {
+                    major: 3,
+                    minor: 14
+                },
+                ExactVersion {
+                    major: 3,
+                    minor: 15
+                }
+            ]
+        );
+        assert!(!config.probe_prereleases);
+        assert_eq!(
+            config.stable,
+            Some(ExactVersion {
+                major: 3,
+                minor: 12
+            })
+        );
+        assert!(config.prefer_system);
+        assert!(!config.supervise);
+        assert_eq!(
+            config.post_exit.as_deref(),
+            Some("echo \"exited with $PY_LAUNCHER_EXIT_STATUS\"")
+        );
+    }
+
+    #[test]
+    fn parse_unknown_setting() {
+        // Settings outside of their section are unknown and only warned about.
+        let config = Config::parse(Path::new("py.ini"), "dirs = venv\n").unwrap();
+
+        assert_eq!(config.venv_dirs, vec![DEFAULT_VENV_DIR.to_string()]);
+    }
+
+    #[test_case("[venv]\nsearch = sometimes" ; "invalid value")]
+    #[test_case("[venv]\ndirs" ; "missing equals sign")]
+    #[test_case("[search]\nposition = middle" ; "invalid position")]
+    #[test_case("[search]\nstable = 3" ; "stable without minor version")]
+    #[test_case("[search]\nprereleases = 3.14, next" ; "invalid pre-release")]
+    fn parse_errors(contents: &str) {
+        let result = Config::parse(Path::new("py.ini"), contents);
+
+        assert!(
+            matches!(result, Err(crate::Error::InvalidConfig(location, _)) if location == "py.ini:2")
+        );
+    }
+}
@@ -0,0 +1,843 @@
+//! Diagnosing why the Launcher may not choose the expected interpreter
+//!
+//! A [`Report`] is made up of [`Check`]s, each of which either passes, warns
+//! about something which may lead to a surprising interpreter being chosen, or
+//! fails due to something which will keep the Launcher from working.
+
+use std::{
+    collections::HashMap,
+    env, fmt,
+    fs::{self, File},
+    io::{BufRead, BufReader, ErrorKind, Read},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    cli::{
+        activated_venv, check_permissions, is_executable, parse_python_shebang, venv_path_search,
+        MAX_SHEBANG_LENGTH, UTF8_BOM,
+    },
+    config::Config,
+    venv::{VirtualEnv, PYVENV_CFG},
+    ComponentSize, ExactVersion, RequestedVersion,
+};
+
+/// The outcome of a [`Check`], ordered by severity.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Status {
+    /// Nothing is wrong.
+    Pass,
+    /// Something may lead to a surprising interpreter being chosen.
+    Warn,
+    /// Something keeps the Launcher from working.
+    Fail,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repr = match self {
+            Self::Pass => "PASS",
+            Self::Warn => "WARN",
+            Self::Fail => "FAIL",
+        };
+        write!(f, "{repr}")
+    }
+}
+
+/// A single diagnostic.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Check {
+    /// The outcome of the check.
+    pub status: Status,
+    /// What was checked (e.g. `PATH`).
+    pub topic: &'static str,
+    /// A human-readable explanation of the outcome.
+    pub message: String,
+}
+
+impl Check {
+    fn pass(topic: &'static str, message: String) -> Self {
+        Self {
+            status: Status::Pass,
+            topic,
+            message,
+        }
+    }
+
+    fn warn(topic: &'static str, message: String) -> Self {
+        Self {
+            status: Status::Warn,
+            topic,
+            message,
+        }
+    }
+
+    fn fail(topic: &'static str, message: String) -> Self {
+        Self {
+            status: Status::Fail,
+            topic,
+            message,
+        }
+    }
+}
+
+static CONFIG_TOPIC: &str = "configuration";
+static PATH_TOPIC: &str = "PATH";
+static INTERPRETERS_TOPIC: &str = "interpreters";
+static VENV_TOPIC: &str = "virtual environment";
+static ENV_VARS_TOPIC: &str = "PY_PYTHON*";
+static SCRIPT_TOPIC: &str = "shebang";
+
+/// The results of every diagnostic.
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Report {
+    /// The checks in the order they were run.
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    /// Runs every check.
+    ///
+    /// The `config` is the result of [`Config::load`] so that an invalid
+    /// configuration is reported instead of stopping the diagnosis; the
+    /// default configuration is then used for the remaining checks. If a
+    /// `script` is specified then its shebang line is checked as well.
+    pub fn run(config: crate::Result<Config>, script: Option<&Path>) -> Self {
+        let mut checks = vec![check_config(&config)];
+        let config = config.unwrap_or_default();
+
+        let path_entries = env::var_os("PATH")
+            .map(|path| env::split_paths(&path).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let executables =
+            executables_by_version(&crate::search_directories(&config), &config.search_exclude);
+
+        checks.extend(check_path(&path_entries));
+        checks.extend(check_interpreters(&executables));
+        checks.extend(check_venvs(&config));
+        checks.extend(check_env_vars(&py_python_env_vars(), &executables));
+        if let Some(script) = script {
+            checks.extend(check_script(script, &executables));
+        }
+
+        Self { checks }
+    }
+
+    /// The most severe status of all the checks.
+    pub fn status(&self) -> Status {
+        self.checks
+            .iter()
+            .map(|check| check.status)
+            .max()
+            .unwrap_or(Status::Pass)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(f, "[{}] {}: {}", check.status, check.topic, check.message)?;
+        }
+
+        let count = |status| {
+            self.checks
+                .iter()
+                .filter(|check| check.status == status)
+                .count()
+        };
+        writeln!(
+            f,
+            "{} passed, {} warned, {} failed",
+            count(Status::Pass),
+            count(Status::Warn),
+            count(Status::Fail)
+        )
+    }
+}
+
+fn check_config(config: &crate::Result<Config>) -> Check {
+    match config {
+        Ok(Config {
+            path: Some(path), ..
+        }) => {
+            let printable_path = path.display();
+            match fs::metadata(path) {
+                Ok(metadata) if metadata.mode() & 0o002 != 0 => Check::warn(
+                    CONFIG_TOPIC,
+                    format!("{printable_path} is writable by everyone"),
+                ),
+                _ => Check::pass(CONFIG_TOPIC, format!("{printable_path} is valid")),
+            }
+        }
+        Ok(Config { path: None, .. }) => match Config::file_path() {
+            Some(path) => {
+                let printable_path = path.display();
+                Check::pass(CONFIG_TOPIC, format!("{printable_path} does not exist"))
+            }
+            None => Check::pass(CONFIG_TOPIC, "no configuration file is used".to_string()),
+        },
+        Err(error) => Check::fail(CONFIG_TOPIC, error.to_string()),
+    }
+}
+
+fn check_path(entries: &[PathBuf]) -> Vec<Check> {
+    if entries.iter().all(|entry| entry.as_os_str().is_empty()) {
+        return vec![Check::fail(PATH_TOPIC, "PATH is empty".to_string())];
+    }
+
+    let mut checks = Vec::new();
+    let mut seen: Vec<(PathBuf, &Path)> = Vec::new();
+    for entry in entries {
+        let printable_entry = entry.display();
+        if entry.as_os_str().is_empty() {
+            checks.push(Check::warn(
+                PATH_TOPIC,
+                "an empty entry causes the current directory to be searched".to_string(),
+            ));
+            continue;
+        } else if entry.is_relative() {
+            checks.push(Check::warn(
+                PATH_TOPIC,
+                format!("{printable_entry} is relative to the current directory"),
+            ));
+        }
+
+        let canonical = entry.canonicalize().unwrap_or_else(|_| entry.clone());
+        if let Some((_, first)) = seen.iter().find(|(path, _)| *path == canonical) {
+            let printable_first = first.display();
+            checks.push(Check::warn(
+                PATH_TOPIC,
+                format!("{printable_entry} is a duplicate of {printable_first}"),
+            ));
+            continue;
+        }
+        seen.push((canonical, entry));
+
+        match fs::read_dir(entry) {
+            Ok(_) => {
+                let mode = fs::metadata(entry).map_or(0, |metadata| metadata.mode());
+                if let Err(reason) = check_permissions(entry, mode) {
+                    checks.push(Check::warn(PATH_TOPIC, reason));
+                }
+            }
+            Err(error) if error.kind() == ErrorKind::NotFound => checks.push(Check::warn(
+                PATH_TOPIC,
+                format!("{printable_entry} does not exist"),
+            )),
+            Err(error) => checks.push(Check::warn(
+                PATH_TOPIC,
+                format!("{printable_entry} cannot be read ({error})"),
+            )),
+        }
+    }
+
+    if checks.is_empty() {
+        let entry_count = entries.len();
+        checks.push(Check::pass(
+            PATH_TOPIC,
+            format!("all {entry_count} entries are readable directories"),
+        ));
+    }
+
+    checks
+}
+
+/// Finds every `pythonX.Y` executable in the directories searched by the
+/// Launcher (see [`crate::search_directories`]), newest version first.
+///
+/// The paths for a version are in the order of the directories, so the first
+/// one is what the Launcher uses while the rest are shadowed by it. Each
+/// directory is only searched once. Executables matching any of `exclusions`
+/// (see [`Config::search_exclude`]) are skipped as the Launcher never uses
+/// them.
+fn executables_by_version(
+    entries: &[PathBuf],
+    exclusions: &[String],
+) -> Vec<(ExactVersion, Vec<PathBuf>)> {
+    let mut executables: HashMap<ExactVersion, Vec<PathBuf>> = HashMap::new();
+    let mut seen = Vec::new();
+    for entry in entries {
+        let canonical = entry.canonicalize().unwrap_or_else(|_| entry.clone());
+        if seen.contains(&canonical) {
+            continue;
+        }
+        seen.push(canonical);
+
+        let Ok(dir) = entry.read_dir() else {
+            continue;
+        };
+        let mut paths = dir
+            .filter_map(|dir_entry| dir_entry.ok())
+            .map(|dir_entry| dir_entry.path())
+            .collect::<Vec<_>>();
+        paths.sort_unstable();
+        for path in paths {
+            if let Ok(version) = ExactVersion::from_path(&path) {
+                if crate::exclusion_for(&path, version, exclusions).is_none() {
+                    executables.entry(version).or_default().push(path);
+                }
+            }
+        }
+    }
+
+    let mut executables = Vec::from_iter(executables);
+    executables.sort_unstable_by(|(a, _), (b, _)| b.cmp(a));
+    executables
+}
+
+/// The directory containing the executable which the Launcher uses for
+/// `version`, if any.
+fn directory_for(
+    executables: &[(ExactVersion, Vec<PathBuf>)],
+    version: RequestedVersion,
+) -> Option<&Path> {
+    executables
+        .iter()
+        .find(|(found, _)| found.supports(version))
+        .and_then(|(_, paths)| paths[0].parent())
+}
+
+fn check_interpreters(executables: &[(ExactVersion, Vec<PathBuf>)]) -> Vec<Check> {
+    if executables.is_empty() {
+        return vec![Check::fail(
+            INTERPRETERS_TOPIC,
+            "no `pythonX.Y` executables were found in the search path".to_string(),
+        )];
+    }
+
+    let versions = executables
+        .iter()
+        .map(|(version, paths)| {
+            let printable_dir = paths[0].parent().unwrap_or(Path::new("")).display();
+            format!("{version} ({printable_dir})")
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut checks = vec![Check::pass(
+        INTERPRETERS_TOPIC,
+        format!("found Python {versions} in the search path"),
+    )];
+
+    for (version, paths) in executables {
+        let chosen = &paths[0];
+        let printable_chosen = chosen.display();
+        if !is_executable(chosen) {
+            checks.push(Check::fail(
+                INTERPRETERS_TOPIC,
+                format!("{printable_chosen} for Python {version} is not executable"),
+            ));
+        }
+        for shadowed in &paths[1..] {
+            let printable_shadowed = shadowed.display();
+            checks.push(Check::warn(
+                INTERPRETERS_TOPIC,
+                format!("{printable_chosen} shadows {printable_shadowed} for Python {version}"),
+            ));
+        }
+    }
+
+    checks
+}
+
+fn check_venvs(config: &Config) -> Vec<Check> {
+    let mut checks = Vec::new();
+    if let Some(venv_path) = activated_venv() {
+        checks.push(check_venv(&venv_path, "activated"));
+        // The activated virtual environment takes precedence over searching.
+        if let Ok(Some(found_path)) = venv_path_search(config, true) {
+            let canonical = |path: &PathBuf| path.canonicalize().unwrap_or_else(|_| path.clone());
+            if canonical(&found_path) != canonical(&venv_path) {
+                let printable_found = found_path.display();
+                checks.push(Check::warn(
+                    VENV_TOPIC,
+                    format!("{printable_found} is not used as the activated virtual environment takes precedence"),
+                ));
+            }
+        }
+        return checks;
+    }
+
+    match venv_path_search(config, true) {
+        Ok(Some(venv_path)) => checks.push(check_venv(&venv_path, "found")),
+        Ok(None) if !config.venv_search => checks.push(Check::pass(
+            VENV_TOPIC,
+            "searching for a virtual environment is disabled".to_string(),
+        )),
+        Ok(None) => checks.push(Check::pass(
+            VENV_TOPIC,
+            "none found in the current or parent directories".to_string(),
+        )),
+        Err(error) => checks.push(Check::fail(VENV_TOPIC, error.to_string())),
+    }
+
+    checks
+}
+
+/// Checks the virtual environment of `executable`, described as how it was
+/// found (e.g. `activated`).
+fn check_venv(executable: &Path, description: &str) -> Check {
+    let printable_executable = executable.display();
+    let Some(venv) = VirtualEnv::from_executable(executable) else {
+        return Check::fail(
+            VENV_TOPIC,
+            format!("the {description} virtual environment has no root directory"),
+        );
+    };
+    let printable_root = venv.root.display();
+
+    if !executable.is_file() {
+        Check::fail(
+            VENV_TOPIC,
+            format!("the {description} virtual environment in {printable_root} is broken as {printable_executable} does not exist"),
+        )
+    } else if !is_executable(executable) {
+        Check::fail(
+            VENV_TOPIC,
+            format!("the {description} virtual environment in {printable_root} is broken as {printable_executable} is not executable"),
+        )
+    } else if !venv.root.join(PYVENV_CFG).is_file() {
+        Check::warn(
+            VENV_TOPIC,
+            format!("the {description} virtual environment in {printable_root} lacks {PYVENV_CFG}"),
+        )
+    } else if let Some(base_executable) = venv
+        .base_executable()
+        .filter(|base_executable| !base_executable.is_file())
+    {
+        let printable_base_executable = base_executable.display();
+        Check::fail(
+            VENV_TOPIC,
+            format!("the {description} virtual environment in {printable_root} was created from {printable_base_executable}, which no longer exists"),
+        )
+    } else {
+        Check::pass(
+            VENV_TOPIC,
+            format!("using the {description} virtual environment in {printable_root}"),
+        )
+    }
+}
+
+/// Gathers `PY_PYTHON` and every `PY_PYTHON{major}` environment variable.
+fn py_python_env_vars() -> Vec<(String, String)> {
+    let mut env_vars = env::vars_os()
+        .filter_map(|(name, value)| {
+            let name = name.into_string().ok()?;
+            let suffix = name.strip_prefix("PY_PYTHON")?;
+            suffix
+                .chars()
+                .all(|c| c.is_ascii_digit())
+                .then(|| (name, value.to_string_lossy().to_string()))
+        })
+        .collect::<Vec<_>>();
+    env_vars.sort_unstable();
+    env_vars
+}
+
+fn check_env_vars(
+    env_vars: &[(String, String)],
+    executables: &[(ExactVersion, Vec<PathBuf>)],
+) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let mut requested = HashMap::new();
+
+    for (name, value) in env_vars.iter().filter(|(_, value)| !value.is_empty()) {
+        let Ok(version) = RequestedVersion::from_str(value) else {
+            checks.push(Check::fail(
+                ENV_VARS_TOPIC,
+                format!("{name}={value:?} is not a version (e.g. `3.6`)"),
+            ));
+            continue;
+        };
+
+        let major_mismatch = match (name["PY_PYTHON".len()..].parse::<ComponentSize>(), version) {
+            (Ok(major), RequestedVersion::MajorOnly(requested_major))
+            | (Ok(major), RequestedVersion::Exact(requested_major, _))
+            | (Ok(major), RequestedVersion::Prerelease(requested_major, _, _)) => {
+                (major != requested_major).then_some(major)
+            }
+            _ => None,
+        };
+
+        if let Some(major) = major_mismatch {
+            checks.push(Check::warn(
+                ENV_VARS_TOPIC,
+                format!("{name}={value:?} makes `py -{major}` use {version}"),
+            ));
+        } else if let Some(dir) = directory_for(executables, version) {
+            let printable_dir = dir.display();
+            checks.push(Check::pass(
+                ENV_VARS_TOPIC,
+                format!("{name}={value:?} requests {version}, found in {printable_dir}"),
+            ));
+        } else {
+            checks.push(Check::warn(
+                ENV_VARS_TOPIC,
+                format!("{name}={value:?} requests {version}, which is not in the search path"),
+            ));
+        }
+        requested.insert(name.as_str(), version);
+    }
+
+    // Only `PY_PYTHON` is used when no version is requested, so a different
+    // version for the same major version means `py` and `py -X` differ.
+    if let Some(
+        default @ (RequestedVersion::MajorOnly(major)
+        | RequestedVersion::Exact(major, _)
+        | RequestedVersion::Prerelease(major, _, _)),
+    ) = requested.get("PY_PYTHON")
+    {
+        let major_env_var = format!("PY_PYTHON{major}");
+        if let Some(major_version) = requested.get(major_env_var.as_str()) {
+            if major_version != default {
+                checks.push(Check::warn(
+                    ENV_VARS_TOPIC,
+                    format!("PY_PYTHON makes `py` use {default} while {major_env_var} makes `py -{major}` use {major_version}"),
+                ));
+            }
+        }
+    }
+
+    if checks.is_empty() {
+        checks.push(Check::pass(
+            ENV_VARS_TOPIC,
+            "no version is requested via an environment variable".to_string(),
+        ));
+    }
+
+    checks
+}
+
+fn check_script(script: &Path, executables: &[(ExactVersion, Vec<PathBuf>)]) -> Vec<Check> {
+    let printable_script = script.display();
+    let mut first_line = Vec::new();
+    if let Err(error) = File::open(script).and_then(|file| {
+        BufReader::new(file.take(MAX_SHEBANG_LENGTH)).read_until(b'\n', &mut first_line)
+    }) {
+        return vec![Check::fail(
+            SCRIPT_TOPIC,
+            format!("{printable_script} cannot be read ({error})"),
+        )];
+    }
+
+    let mut checks = Vec::new();
+    let line = match first_line.strip_prefix(UTF8_BOM) {
+        Some(line) => {
+            checks.push(Check::warn(
+                SCRIPT_TOPIC,
+                format!("{printable_script} starts with a byte order mark, so the operating system cannot run it directly"),
+            ));
+            line
+        }
+        None => &first_line,
+    };
+
+    if !line.starts_with(b"#!") {
+        checks.push(Check::pass(
+            SCRIPT_TOPIC,
+            format!("{printable_script} has no shebang line, so the default interpreter is used"),
+        ));
+        return checks;
+    } else if line.ends_with(b"\r\n") {
+        checks.push(Check::warn(
+            SCRIPT_TOPIC,
+            format!("{printable_script} has a shebang line ending in `\\r\\n`, so the operating system cannot run it directly"),
+        ));
+    }
+
+    let Some(shebang) = parse_python_shebang(&mut first_line.as_slice()) else {
+        checks.push(Check::warn(
+            SCRIPT_TOPIC,
+            format!("{printable_script} has a shebang line which does not launch Python, so it is ignored"),
+        ));
+        return checks;
+    };
+
+    let version = shebang.requested_version;
+    match shebang.absolute_interpreter() {
+        Some(interpreter) if is_executable(interpreter) => {
+            let printable_interpreter = interpreter.display();
+            checks.push(Check::pass(
+                SCRIPT_TOPIC,
+                format!("{printable_script} launches {printable_interpreter}"),
+            ));
+            return checks;
+        }
+        Some(interpreter) => {
+            let printable_interpreter = interpreter.display();
+            checks.push(Check::warn(
+                SCRIPT_TOPIC,
+                format!("{printable_script} specifies {printable_interpreter}, which is not an executable, so {version} is searched for instead"),
+            ));
+        }
+        None => {}
+    }
+
+    if let Some(dir) = directory_for(executables, version) {
+        let printable_dir = dir.display();
+        checks.push(Check::pass(
+            SCRIPT_TOPIC,
+            format!("{printable_script} requests {version}, found in {printable_dir}"),
+        ));
+    } else {
+        checks.push(Check::fail(
+            SCRIPT_TOPIC,
+            format!("{printable_script} requests {version}, which is not in the search path"),
+        ));
+    }
+
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use test_case::test_case;
+
+    use super::*;
+
+    fn statuses(checks: &[Check]) -> Vec<Status> {
+        checks.iter().map(|check| check.status).collect()
+    }
+
+    fn make_executable(path: &Path) {
+        fs::write(path, "").unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn report_status_and_display() {
+        let report = Report {
+            checks: vec![
+                Check::pass(PATH_TOPIC, "fine".to_string()),
+                Check::warn(ENV_VARS_TOPIC, "hmm".to_string()),
+            ],
+        };
+
+        assert_eq!(report.status(), Status::Warn);
+        assert_eq!(
+            report.to_string(),
+            "[PASS] PATH: fine\n[WARN] PY_PYTHON*: hmm\n1 passed, 1 warned, 0 failed\n"
+        );
+        assert_eq!(Report::default().status(), Status::Pass);
+    }
+
+    #[test]
+    fn check_path_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+        let unsafe_dir = dir.path().join("unsafe");
+        fs::create_dir(&unsafe_dir).unwrap();
+        fs::set_permissions(&unsafe_dir, fs::Permissions::from_mode(0o777)).unwrap();
+
+        assert_eq!(
+            statuses(&check_path(&[dir.path().to_path_buf()])),
+            [Status::Pass]
+        );
+        assert_eq!(statuses(&check_path(&[])), [Status::Fail]);
+
+        let checks = check_path(&[
+            dir.path().to_path_buf(),
+            PathBuf::new(),
+            PathBuf::from("relative"),
+            dir.path().join("."),
+            missing,
+            unsafe_dir,
+        ]);
+        let messages = checks
+            .iter()
+            .map(|check| check.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(statuses(&checks), [Status::Warn; 6]);
+        assert!(messages[0].contains("empty entry"));
+        assert!(messages[1].contains("relative"));
+        assert_eq!(messages[2], "relative does not exist");
+        assert!(messages[3].contains("is a duplicate of"));
+        assert!(messages[4].ends_with("does not exist"));
+        assert!(messages[5].contains("writable by everyone"));
+    }
+
+    #[test]
+    fn check_shadowed_interpreters() {
+        let dir1 = tempfile::tempdir().unwrap();
+        let dir2 = tempfile::tempdir().unwrap();
+        make_executable(&dir1.path().join("python3.11"));
+        make_executable(&dir2.path().join("python3.11"));
+        fs::write(dir2.path().join("python3.12"), "").unwrap();
+
+        let entries = [
+            dir1.path().to_path_buf(),
+            dir2.path().to_path_buf(),
+            dir1.path().to_path_buf(),
+        ];
+        let executables = executables_by_version(&entries, &[]);
+        assert_eq!(
+            executables,
+            [
+                (
+                    ExactVersion::new(3, 12),
+                    vec![dir2.path().join("python3.12")]
+                ),
+                (
+                    ExactVersion::new(3, 11),
+                    vec![
+                        dir1.path().join("python3.11"),
+                        dir2.path().join("python3.11")
+                    ]
+                ),
+            ]
+        );
+
+        let checks = check_interpreters(&executables);
+        assert_eq!(
+            statuses(&checks),
+            [Status::Pass, Status::Fail, Status::Warn]
+        );
+        assert_eq!(
+            checks[0].message,
+            format!(
+                "found Python 3.12 ({}), 3.11 ({}) in the search path",
+                dir2.path().display(),
+                dir1.path().display()
+            )
+        );
+        assert!(checks[1].message.ends_with("is not executable"));
+        assert!(checks[2].message.contains(" shadows "));
+
+        assert_eq!(statuses(&check_interpreters(&[])), [Status::Fail]);
+
+        // Excluded interpreters are neither used nor shadowed.
+        let exclusions = [dir1.path().join("python3.11").display().to_string()];
+        let executables = executables_by_version(&entries, &exclusions);
+        assert_eq!(
+            executables,
+            [
+                (
+                    ExactVersion::new(3, 12),
+                    vec![dir2.path().join("python3.12")]
+                ),
+                (
+                    ExactVersion::new(3, 11),
+                    vec![dir2.path().join("python3.11")]
+                ),
+            ]
+        );
+        assert_eq!(
+            statuses(&check_interpreters(&executables)),
+            [Status::Pass, Status::Fail]
+        );
+    }
+
+    #[test]
+    fn check_venv_broken() {
+        let dir = tempfile::tempdir().unwrap();
+        let bin_dir = dir.path().join("bin");
+        fs::create_dir(&bin_dir).unwrap();
+        let python = bin_dir.join("python");
+
+        assert_eq!(check_venv(&python, "activated").status, Status::Fail);
+
+        make_executable(&python);
+        assert_eq!(check_venv(&python, "activated").status, Status::Warn);
+
+        let base_executable = dir.path().join("missing-python");
+        let printable_base_executable = base_executable.display();
+        fs::write(
+            dir.path().join(PYVENV_CFG),
+            format!("executable = {printable_base_executable}\n"),
+        )
+        .unwrap();
+        let check = check_venv(&python, "activated");
+        assert_eq!(check.status, Status::Fail);
+        assert!(check.message.ends_with("which no longer exists"));
+
+        make_executable(&base_executable);
+        assert_eq!(check_venv(&python, "activated").status, Status::Pass);
+    }
+
+    fn executables() -> Vec<(ExactVersion, Vec<PathBuf>)> {
+        vec![
+            (
+                ExactVersion::new(3, 12),
+                vec![PathBuf::from("/opt/python/bin/python3.12")],
+            ),
+            (
+                ExactVersion::new(3, 11),
+                vec![PathBuf::from("/usr/bin/python3.11")],
+            ),
+        ]
+    }
+
+    #[test]
+    fn check_env_vars_directory() {
+        let env_vars = [("PY_PYTHON".to_string(), "3.11".to_string())];
+        let checks = check_env_vars(&env_vars, &executables());
+        assert_eq!(
+            checks[0].message,
+            "PY_PYTHON=\"3.11\" requests Python 3.11, found in /usr/bin"
+        );
+
+        let env_vars = [("PY_PYTHON".to_string(), "3.9".to_string())];
+        let checks = check_env_vars(&env_vars, &executables());
+        assert!(checks[0]
+            .message
+            .ends_with("which is not in the search path"));
+    }
+
+    #[test_case(&[] => vec![Status::Pass] ; "nothing set")]
+    #[test_case(&[("PY_PYTHON", "")] => vec![Status::Pass] ; "empty")]
+    #[test_case(&[("PY_PYTHON", "3.11")] => vec![Status::Pass] ; "available")]
+    #[test_case(&[("PY_PYTHON", "3.9")] => vec![Status::Warn] ; "unavailable")]
+    #[test_case(&[("PY_PYTHON", "3.x")] => vec![Status::Fail] ; "invalid")]
+    #[test_case(&[("PY_PYTHON3", "2.7")] => vec![Status::Warn] ; "wrong major version")]
+    #[test_case(&[("PY_PYTHON", "3.11"), ("PY_PYTHON3", "3.11")] => vec![Status::Pass, Status::Pass] ; "agreeing")]
+    #[test_case(&[("PY_PYTHON", "3"), ("PY_PYTHON3", "3.11")] => vec![Status::Pass, Status::Pass, Status::Warn] ; "major only conflict")]
+    #[test_case(&[("PY_PYTHON", "3.12"), ("PY_PYTHON3", "3.11")] => vec![Status::Pass, Status::Pass, Status::Warn] ; "exact conflict")]
+    #[test_case(&[("PY_PYTHON", "3.12"), ("PY_PYTHON2", "2.7")] => vec![Status::Pass, Status::Warn] ; "different major versions")]
+    fn check_env_vars_tests(env_vars: &[(&str, &str)]) -> Vec<Status> {
+        let env_vars = env_vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<Vec<_>>();
+        statuses(&check_env_vars(&env_vars, &executables()))
+    }
+
+    #[test_case("print('hi')\n" => vec![Status::Pass] ; "no shebang")]
+    #[test_case("#! /usr/bin/env python3.11\n" => vec![Status::Pass] ; "available version")]
+    #[test_case("#! /usr/bin/python3.9\n" => vec![Status::Fail] ; "unavailable version")]
+    #[test_case("#! /usr/bin/env python3\r\n" => vec![Status::Warn, Status::Pass] ; "crlf")]
+    #[test_case("\u{feff}#! /usr/bin/python3\n" => vec![Status::Warn, Status::Pass] ; "bom")]
+    #[test_case("#! /bin/sh\n" => vec![Status::Warn] ; "not python")]
+    #[test_case("#! /does/not/exist/python3.11\n" => vec![Status::Warn, Status::Pass] ; "missing interpreter")]
+    fn check_script_tests(contents: &str) -> Vec<Status> {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("script.py");
+        fs::write(&script, contents).unwrap();
+        statuses(&check_script(&script, &executables()))
+    }
+
+    #[test]
+    fn check_script_unreadable() {
+        let dir = tempfile::tempdir().unwrap();
+        let checks = check_script(&dir.path().join("missing.py"), &[]);
+        assert_eq!(statuses(&checks), [Status::Fail]);
+    }
+
+    #[test]
+    fn check_script_absolute_interpreter() {
+        let dir = tempfile::tempdir().unwrap();
+        let interpreter = dir.path().join("python3");
+        make_executable(&interpreter);
+        let script = dir.path().join("script.py");
+        let printable_interpreter = interpreter.display();
+        fs::write(&script, format!("#!{printable_interpreter}\n")).unwrap();
+
+        let checks = check_script(&script, &[]);
+        assert_eq!(statuses(&checks), [Status::Pass]);
+        assert!(checks[0]
+            .message
+            .ends_with(&format!("launches {printable_interpreter}")));
+    }
+}
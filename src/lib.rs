@@ -14,20 +14,63 @@
 //! The [`cli`] module contains all code related to providing a CLI like the one
 //! the [Python Launcher for Windows] provides.
 //!
+//! The [`completions`] module generates shell completions for the CLI.
+//!
+//! The [`config`] module handles the configuration file and environment
+//! variables which customize the Launcher.
+//!
+//! The [`doctor`] module diagnoses why the Launcher may not choose the
+//! expected interpreter.
+//!
+//! The [`project`] module finds the Python version a project specifies.
+//!
+//! The [`shell_init`] module keeps a shell's virtual environment in sync with
+//! the Launcher.
+//!
+//! The [`venv`] module contains code for inspecting virtual environments.
+//!
 //! [Python Launcher for Windows]: https://docs.python.org/3/using/windows.html#launcher
 
 pub mod cli;
+pub mod completions;
+pub mod config;
+pub mod doctor;
+pub mod project;
+pub mod shell_init;
+pub mod venv;
 
 use std::{
+    cell::RefCell,
     collections::HashMap,
     convert::From,
     env, fmt,
     fmt::Display,
     num::ParseIntError,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
+    process::Command,
     str::FromStr,
 };
 
+use crate::config::{Config, SearchPosition};
+
+/// Exit code when no Python executable could be found.
+///
+/// The exit codes specific to the Launcher are in the range of 200 to 209 so
+/// they are distinct from what Python itself (e.g. `1`, `2`, and `120`), the
+/// conventions of `sysexits.h` (`64` to `78`), the shell (`126` and `127`), and
+/// termination by a signal (`128` plus the signal number) use.
+pub const EXIT_NO_EXECUTABLE: exitcode::ExitCode = 200;
+
+/// Exit code when the virtual environment to use is broken (e.g. its
+/// executable does not exist).
+pub const EXIT_BROKEN_VENV: exitcode::ExitCode = 201;
+
+/// Exit code when the configuration is invalid.
+pub const EXIT_INVALID_CONFIG: exitcode::ExitCode = 202;
+
+/// Exit code when executing the Python executable failed.
+pub const EXIT_EXEC_FAILED: exitcode::ExitCode = 203;
+
 /// [`std::result::Result`] type with [`Error`] as the error type.
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -44,10 +87,120 @@ pub enum Error {
     FileNameToStrError,
     /// A file name is not structured appropriately.
     PathFileNameError,
-    /// No Python executable could be found based on the constraints provided.
-    NoExecutableFound(RequestedVersion),
+    /// No Python executable could be found based on the constraints provided;
+    /// includes the context of the search to help explain why.
+    NoExecutableFound(RequestedVersion, SearchContext),
     /// An illegal combination of CLI flags are provided.
     IllegalArgument(PathBuf, String),
+    /// A CLI argument is not recognized by the Launcher.
+    UnrecognizedArgument(PathBuf, String),
+    /// A configuration setting is invalid; includes where the setting came
+    /// from along with what is wrong.
+    InvalidConfig(String, String),
+    /// A template for formatting output is invalid; includes the template
+    /// along with what is wrong.
+    InvalidTemplate(String, String),
+    /// A virtual environment found via a search is not safe to execute; includes
+    /// the path to the executable along with why it is unsafe.
+    UnsafeVenv(PathBuf, String),
+    /// The executable of the activated virtual environment does not exist.
+    BrokenVenv(PathBuf),
+    /// A virtual environment was to be created where one already exists.
+    VenvExists(PathBuf),
+    /// A directory which is not a virtual environment was to be replaced by
+    /// one.
+    NotAVenv(PathBuf),
+    /// No virtual environment was found when one is required.
+    VenvNotFound,
+    /// A command run by the Launcher failed; includes the command along with
+    /// why it failed.
+    CommandFailed(String, String),
+    /// Executing the Python executable failed; includes the executable along
+    /// with why it failed.
+    ExecFailed(PathBuf, String),
+}
+
+/// The context of a search for a Python executable which found nothing.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchContext {
+    /// What requested the version when it was not the command line, e.g.
+    /// `PY_PYTHON`.
+    pub requested_by: Option<String>,
+    /// The directories which were searched.
+    pub directories: Vec<PathBuf>,
+    /// The versions of the executables which were found, newest first.
+    pub available: Vec<ExactVersion>,
+}
+
+impl SearchContext {
+    /// Gathers the context of searching `PATH` (along with the directories of
+    /// the configured [`Config::search_path`]).
+    pub fn from_path(requested_by: Option<String>) -> Self {
+        Self::from_path_with_config(requested_by, &load_config())
+    }
+
+    /// Gathers the context of searching `PATH` along with the directories of
+    /// [`Config::search_path`].
+    pub fn from_path_with_config(requested_by: Option<String>, config: &Config) -> Self {
+        let mut available = all_executables_with_config(config)
+            .into_keys()
+            .collect::<Vec<_>>();
+        available.sort_unstable_by(|a, b| b.cmp(a));
+        Self {
+            requested_by,
+            directories: search_directories(config),
+            available,
+        }
+    }
+
+    /// Suggests the version flag for the available version which is closest
+    /// to what was requested.
+    ///
+    /// For a `major.minor` version, the closest minor version of the same
+    /// major version is preferred (favouring the newer version on a tie);
+    /// otherwise the newest version available is suggested.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use python_launcher::{ExactVersion, RequestedVersion, SearchContext};
+    ///
+    /// let context = SearchContext {
+    ///     available: vec![ExactVersion::new(3, 12), ExactVersion::new(3, 11)],
+    ///     ..SearchContext::default()
+    /// };
+    ///
+    /// assert_eq!(
+    ///     context.suggestion(RequestedVersion::Exact(3, 9)),
+    ///     Some("-3.11".to_string())
+    /// );
+    /// assert_eq!(
+    ///     context.suggestion(RequestedVersion::MajorOnly(2)),
+    ///     Some("-3".to_string())
+    /// );
+    /// ```
+    pub fn suggestion(&self, requested: RequestedVersion) -> Option<String> {
+        let newest = self.available.first()?;
+        match requested {
+            RequestedVersion::Any => None,
+            RequestedVersion::MajorOnly(_) => Some(format!("-{}", newest.major)),
+            RequestedVersion::Exact(major, minor)
+            | RequestedVersion::Prerelease(major, minor, _) => {
+                let closest = self
+                    .available
+                    .iter()
+                    .filter(|version| version.major == major)
+                    .min_by_key(|version| {
+                        (
+                            version.minor.abs_diff(minor),
+                            std::cmp::Reverse(version.minor),
+                        )
+                    })
+                    .unwrap_or(newest);
+                Some(format!("-{closest}"))
+            }
+        }
+    }
 }
 
 impl fmt::Display for Error {
@@ -60,8 +213,39 @@ impl fmt::Display for Error {
             Self::FileNameMissing => write!(f, "Path object lacks a file name"),
             Self::FileNameToStrError => write!(f, "Failed to convert file name to `str`"),
             Self::PathFileNameError => write!(f, "File name not of the format `pythonX.Y`"),
-            Self::NoExecutableFound(requested_version) => {
-                write!(f, "No executable found for {requested_version}")
+            Self::NoExecutableFound(requested_version, context) => {
+                write!(f, "No executable found for {requested_version}")?;
+                if let Some(requested_by) = &context.requested_by {
+                    write!(f, " (as requested by {requested_by})")?;
+                }
+                if context.available.is_empty() {
+                    let directories = context
+                        .directories
+                        .iter()
+                        .map(|directory| directory.display().to_string())
+                        .collect::<Vec<_>>();
+                    if directories.is_empty() {
+                        write!(f, "; PATH is empty")
+                    } else {
+                        let printable_directories = directories.join(", ");
+                        write!(
+                            f,
+                            "; no Python executables found in {printable_directories}"
+                        )
+                    }
+                } else {
+                    let available = context
+                        .available
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    write!(f, "; available: {available}")?;
+                    match context.suggestion(*requested_version) {
+                        Some(flag) => write!(f, "; did you mean `{flag}`?"),
+                        None => Ok(()),
+                    }
+                }
             }
             Self::IllegalArgument(launcher_path, flag) => {
                 let printable_path = launcher_path.to_string_lossy();
@@ -70,6 +254,58 @@ impl fmt::Display for Error {
                     "The `{flag}` flag must be specified on its own; see `{printable_path} --help` for details"
                 )
             }
+            Self::UnrecognizedArgument(launcher_path, argument) => {
+                let printable_path = launcher_path.to_string_lossy();
+                write!(
+                    f,
+                    "Unrecognized argument `{argument}`; see `{printable_path} --help` for details"
+                )
+            }
+            Self::InvalidConfig(location, message) => {
+                write!(f, "Invalid configuration in {location}: {message}")
+            }
+            Self::InvalidTemplate(template, message) => {
+                write!(f, "Invalid template {template:?}: {message}")
+            }
+            Self::UnsafeVenv(executable, reason) => {
+                let printable_executable = executable.display();
+                write!(
+                    f,
+                    "Refusing to use the virtual environment at {printable_executable} as {reason}; set PY_LAUNCHER_VENV_ALLOW_UNSAFE=1 to use it anyway"
+                )
+            }
+            Self::BrokenVenv(executable) => {
+                let printable_executable = executable.display();
+                write!(
+                    f,
+                    "The activated virtual environment is broken as {printable_executable} does not exist"
+                )
+            }
+            Self::VenvExists(dir) => {
+                let printable_dir = dir.display();
+                write!(
+                    f,
+                    "{printable_dir} already exists; use `--force` to replace it"
+                )
+            }
+            Self::NotAVenv(dir) => {
+                let printable_dir = dir.display();
+                write!(
+                    f,
+                    "{printable_dir} is not a virtual environment, so it will not be replaced"
+                )
+            }
+            Self::VenvNotFound => write!(
+                f,
+                "No virtual environment found in the current or parent directories"
+            ),
+            Self::CommandFailed(command, reason) => {
+                write!(f, "Running {command} failed: {reason}")
+            }
+            Self::ExecFailed(executable, reason) => {
+                let printable_executable = executable.display();
+                write!(f, "Executing {printable_executable} failed: {reason}")
+            }
         }
     }
 }
@@ -82,8 +318,18 @@ impl std::error::Error for Error {
             Self::FileNameMissing => None,
             Self::FileNameToStrError => None,
             Self::PathFileNameError => None,
-            Self::NoExecutableFound(_) => None,
+            Self::NoExecutableFound(_, _) => None,
             Self::IllegalArgument(_, _) => None,
+            Self::UnrecognizedArgument(_, _) => None,
+            Self::InvalidConfig(_, _) => None,
+            Self::InvalidTemplate(_, _) => None,
+            Self::UnsafeVenv(_, _) => None,
+            Self::BrokenVenv(_) => None,
+            Self::VenvExists(_) => None,
+            Self::NotAVenv(_) => None,
+            Self::VenvNotFound => None,
+            Self::CommandFailed(_, _) => None,
+            Self::ExecFailed(_, _) => None,
         }
     }
 }
@@ -97,10 +343,174 @@ impl Error {
             Self::FileNameMissing => exitcode::USAGE,
             Self::FileNameToStrError => exitcode::SOFTWARE,
             Self::PathFileNameError => exitcode::SOFTWARE,
-            Self::NoExecutableFound(_) => exitcode::USAGE,
+            Self::NoExecutableFound(_, _) => EXIT_NO_EXECUTABLE,
             Self::IllegalArgument(_, _) => exitcode::USAGE,
+            Self::UnrecognizedArgument(_, _) => exitcode::USAGE,
+            Self::InvalidConfig(_, _) => EXIT_INVALID_CONFIG,
+            Self::InvalidTemplate(_, _) => exitcode::USAGE,
+            Self::UnsafeVenv(_, _) => exitcode::NOPERM,
+            Self::BrokenVenv(_) => EXIT_BROKEN_VENV,
+            Self::VenvExists(_) => exitcode::CANTCREAT,
+            Self::NotAVenv(_) => exitcode::CANTCREAT,
+            Self::VenvNotFound => exitcode::NOINPUT,
+            Self::CommandFailed(_, _) => exitcode::UNAVAILABLE,
+            Self::ExecFailed(_, _) => EXIT_EXEC_FAILED,
+        }
+    }
+
+    /// Returns a stable identifier for the kind of error, e.g.
+    /// `no_executable_found`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::ParseVersionComponentError(_, _) => "parse_version_component",
+            Self::DotMissing => "dot_missing",
+            Self::FileNameMissing => "file_name_missing",
+            Self::FileNameToStrError => "file_name_to_str",
+            Self::PathFileNameError => "path_file_name",
+            Self::NoExecutableFound(_, _) => "no_executable_found",
+            Self::IllegalArgument(_, _) => "illegal_argument",
+            Self::UnrecognizedArgument(_, _) => "unrecognized_argument",
+            Self::InvalidConfig(_, _) => "invalid_config",
+            Self::InvalidTemplate(_, _) => "invalid_template",
+            Self::UnsafeVenv(_, _) => "unsafe_venv",
+            Self::BrokenVenv(_) => "broken_venv",
+            Self::VenvExists(_) => "venv_exists",
+            Self::NotAVenv(_) => "not_a_venv",
+            Self::VenvNotFound => "venv_not_found",
+            Self::CommandFailed(_, _) => "command_failed",
+            Self::ExecFailed(_, _) => "exec_failed",
         }
     }
+
+    /// Formats the error as a JSON object for tools to consume.
+    ///
+    /// The object contains the error's [`kind`](Error::kind), the message, the
+    /// exit code the Launcher exits with, and any context specific to the
+    /// kind of error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let error = python_launcher::Error::VenvExists(".venv".into());
+    ///
+    /// assert_eq!(
+    ///     error.to_json(73),
+    ///     r#"{"kind":"venv_exists","message":".venv already exists; use `--force` to replace it","exit_code":73,"context":{"path":".venv"}}"#
+    /// );
+    /// ```
+    pub fn to_json(&self, exit_code: i32) -> String {
+        let context = match self {
+            Self::ParseVersionComponentError(_, value) => vec![("value", json_string(value))],
+            Self::DotMissing
+            | Self::FileNameMissing
+            | Self::FileNameToStrError
+            | Self::PathFileNameError
+            | Self::VenvNotFound => Vec::new(),
+            Self::NoExecutableFound(requested_version, context) => {
+                let requested = match requested_version {
+                    RequestedVersion::Any => "null".to_string(),
+                    RequestedVersion::MajorOnly(major) => json_string(&major.to_string()),
+                    RequestedVersion::Exact(major, minor) => {
+                        json_string(&format!("{major}.{minor}"))
+                    }
+                    RequestedVersion::Prerelease(major, minor, level) => {
+                        json_string(&format!("{major}.{minor}{level}"))
+                    }
+                };
+                vec![
+                    ("requested_version", requested),
+                    (
+                        "requested_by",
+                        json_optional_string(context.requested_by.as_deref()),
+                    ),
+                    (
+                        "directories",
+                        json_array(
+                            context
+                                .directories
+                                .iter()
+                                .map(|directory| directory.display().to_string()),
+                        ),
+                    ),
+                    (
+                        "available",
+                        json_array(context.available.iter().map(ToString::to_string)),
+                    ),
+                    (
+                        "suggestion",
+                        json_optional_string(context.suggestion(*requested_version).as_deref()),
+                    ),
+                ]
+            }
+            Self::IllegalArgument(launcher_path, argument)
+            | Self::UnrecognizedArgument(launcher_path, argument) => vec![
+                (
+                    "launcher",
+                    json_string(&launcher_path.display().to_string()),
+                ),
+                ("argument", json_string(argument)),
+            ],
+            Self::InvalidConfig(location, _) => vec![("location", json_string(location))],
+            Self::InvalidTemplate(template, _) => vec![("template", json_string(template))],
+            Self::UnsafeVenv(path, reason) => vec![
+                ("path", json_string(&path.display().to_string())),
+                ("reason", json_string(reason)),
+            ],
+            Self::BrokenVenv(executable) => {
+                vec![("executable", json_string(&executable.display().to_string()))]
+            }
+            Self::VenvExists(path) | Self::NotAVenv(path) => {
+                vec![("path", json_string(&path.display().to_string()))]
+            }
+            Self::CommandFailed(command, reason) => vec![
+                ("command", json_string(command)),
+                ("reason", json_string(reason)),
+            ],
+            Self::ExecFailed(executable, reason) => vec![
+                ("executable", json_string(&executable.display().to_string())),
+                ("reason", json_string(reason)),
+            ],
+        };
+        let context = context
+            .into_iter()
+            .map(|(key, value)| format!("{}:{value}", json_string(key)))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!(
+            r#"{{"kind":{},"message":{},"exit_code":{exit_code},"context":{{{context}}}}}"#,
+            json_string(self.kind()),
+            json_string(&self.to_string())
+        )
+    }
+}
+
+/// Formats a string as a JSON string, escaping as necessary.
+fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn json_optional_string(value: Option<&str>) -> String {
+    value.map_or_else(|| "null".to_string(), json_string)
+}
+
+fn json_array(values: impl Iterator<Item = String>) -> String {
+    let values = values.map(|value| json_string(&value)).collect::<Vec<_>>();
+    format!("[{}]", values.join(","))
 }
 
 /// The integral part of a version specifier (e.g. the `3` or `10` of `3.10`).
@@ -119,6 +529,9 @@ pub enum RequestedVersion {
     MajorOnly(ComponentSize),
     /// A specific `major.minor` version of Python is required (e.g. `3.9`).
     Exact(ComponentSize, ComponentSize),
+    /// A specific pre-release of a `major.minor` version of Python is
+    /// required (e.g. `3.14rc`).
+    Prerelease(ComponentSize, ComponentSize, ReleaseLevel),
 }
 
 impl Display for RequestedVersion {
@@ -128,6 +541,7 @@ impl Display for RequestedVersion {
             Self::Any => "Python".to_string(),
             Self::MajorOnly(major) => format!("Python {major}"),
             Self::Exact(major, minor) => format!("Python {major}.{minor}"),
+            Self::Prerelease(major, minor, level) => format!("Python {major}.{minor}{level}"),
         };
         write!(f, "{repr}")
     }
@@ -137,7 +551,17 @@ impl FromStr for RequestedVersion {
     type Err = Error;
 
     fn from_str(version_string: &str) -> Result<Self> {
-        if version_string.is_empty() {
+        let prerelease = version_string
+            .find(|c: char| c.is_ascii_alphabetic())
+            .and_then(|index| {
+                let level = ReleaseLevel::from_suffix(&version_string[index..])?;
+                let version = ExactVersion::from_str(&version_string[..index]).ok()?;
+                Some(Self::Prerelease(version.major, version.minor, level))
+            });
+
+        if let Some(prerelease) = prerelease {
+            Ok(prerelease)
+        } else if version_string.is_empty() {
             Ok(Self::Any)
         } else if version_string.contains('.') {
             let exact_version = ExactVersion::from_str(version_string)?;
@@ -197,6 +621,73 @@ impl RequestedVersion {
     }
 }
 
+/// The release level of a Python executable, i.e.
+/// `sys.version_info.releaselevel`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum ReleaseLevel {
+    /// An alpha release (e.g. `3.14.0a1`).
+    Alpha,
+    /// A beta release (e.g. `3.14.0b1`).
+    Beta,
+    /// A release candidate (e.g. `3.14.0rc1`).
+    Candidate,
+    /// A final release.
+    Final,
+}
+
+impl Display for ReleaseLevel {
+    /// Format to the suffix used in version numbers, e.g. `rc`; a final
+    /// release has no suffix.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let suffix = match self {
+            Self::Alpha => "a",
+            Self::Beta => "b",
+            Self::Candidate => "rc",
+            Self::Final => "",
+        };
+        write!(f, "{suffix}")
+    }
+}
+
+impl ReleaseLevel {
+    /// Parses the suffix of a pre-release version, e.g. the `rc` of `3.14rc`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use python_launcher::ReleaseLevel;
+    ///
+    /// assert_eq!(ReleaseLevel::from_suffix("rc"), Some(ReleaseLevel::Candidate));
+    /// assert_eq!(ReleaseLevel::from_suffix("beta"), Some(ReleaseLevel::Beta));
+    /// assert_eq!(ReleaseLevel::from_suffix("final"), None);
+    /// ```
+    pub fn from_suffix(suffix: &str) -> Option<Self> {
+        match suffix {
+            "a" | "alpha" => Some(Self::Alpha),
+            "b" | "beta" => Some(Self::Beta),
+            "rc" | "c" | "candidate" => Some(Self::Candidate),
+            _ => None,
+        }
+    }
+
+    /// Runs the executable at `path` to get its release level.
+    pub fn probe(path: &Path) -> Option<Self> {
+        let printable_path = path.display();
+        log::info!("Probing {printable_path} for its release level");
+        let output = Command::new(path)
+            .args(["-c", "import sys; print(sys.version_info.releaselevel)"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())?;
+        let release_level = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        log::debug!("{printable_path} has a release level of {release_level:?}");
+        match release_level.as_str() {
+            "final" => Some(Self::Final),
+            level => Self::from_suffix(level),
+        }
+    }
+}
+
 /// Specifies the `major.minor` version of a Python executable.
 ///
 /// This struct is typically used to represent a found executable's version.
@@ -300,6 +791,10 @@ impl ExactVersion {
 
     /// Tests whether this [`ExactVersion`] satisfies the [`RequestedVersion`].
     ///
+    /// Only the `major.minor` version is compared for a
+    /// [`RequestedVersion::Prerelease`], as the release level is not part of an
+    /// executable's name.
+    ///
     /// # Examples
     ///
     /// ```
@@ -316,7 +811,8 @@ impl ExactVersion {
         match requested {
             RequestedVersion::Any => true,
             RequestedVersion::MajorOnly(major_version) => self.major == major_version,
-            RequestedVersion::Exact(major_version, minor_version) => {
+            RequestedVersion::Exact(major_version, minor_version)
+            | RequestedVersion::Prerelease(major_version, minor_version, _) => {
                 self.major == major_version && self.minor == minor_version
             }
         }
@@ -333,6 +829,97 @@ fn env_path() -> Vec<PathBuf> {
     }
 }
 
+/// Whether `name` matches the glob `pattern`, where `*` matches any number of
+/// characters and `?` matches a single character.
+///
+/// Like a shell, a leading `.` in `name` must be matched explicitly.
+fn matches_glob(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match (pattern.split_first(), name.split_first()) {
+            (None, _) => name.is_empty(),
+            (Some(('*', rest)), _) => {
+                matches(rest, name) || (!name.is_empty() && matches(pattern, &name[1..]))
+            }
+            (Some(('?', rest)), Some((_, name_rest))) => matches(rest, name_rest),
+            (Some((expected, rest)), Some((actual, name_rest))) => {
+                expected == actual && matches(rest, name_rest)
+            }
+            (Some(_), None) => false,
+        }
+    }
+
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    matches(&pattern, &name)
+}
+
+/// Expands the glob patterns in `pattern` into the existing directories they
+/// match, sorted by name.
+///
+/// A path without a glob pattern is returned as-is, whether it exists or not.
+fn expand_glob(pattern: &Path) -> Vec<PathBuf> {
+    let mut expanded = vec![PathBuf::new()];
+    for component in pattern.components() {
+        let glob = match component {
+            Component::Normal(name) => name.to_str().filter(|name| name.contains(['*', '?'])),
+            _ => None,
+        };
+        let Some(glob) = glob else {
+            for path in &mut expanded {
+                path.push(component);
+            }
+            continue;
+        };
+
+        expanded = expanded
+            .into_iter()
+            .flat_map(|parent| {
+                let dir = if parent.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    parent.as_path()
+                };
+                let mut matches = dir
+                    .read_dir()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.path().is_dir())
+                    .map(|entry| entry.file_name())
+                    .filter(|name| name.to_str().map_or(false, |name| matches_glob(glob, name)))
+                    .map(|name| parent.join(name))
+                    .collect::<Vec<_>>();
+                matches.sort_unstable();
+                matches
+            })
+            .collect();
+    }
+
+    expanded
+}
+
+/// The directories to search for Python executables, in order.
+///
+/// The entries of [`Config::search_path`] (with their glob patterns expanded)
+/// are placed before or after those of `PATH` based on
+/// [`Config::search_position`].
+pub fn search_directories(config: &Config) -> Vec<PathBuf> {
+    let search_path = config
+        .search_path
+        .iter()
+        .flat_map(|pattern| expand_glob(Path::new(pattern)))
+        .collect::<Vec<_>>();
+    let path = env_path();
+
+    match config.search_position {
+        SearchPosition::Before => [search_path, path].concat(),
+        SearchPosition::After => [path, search_path].concat(),
+    }
+}
+
 fn flatten_directories(
     directories: impl IntoIterator<Item = PathBuf>,
 ) -> impl Iterator<Item = PathBuf> {
@@ -344,13 +931,56 @@ fn flatten_directories(
         .map(|e| e.path()) // Get the PathBuf from the DirEntry.
 }
 
+/// Whether the executable at `path` for `version` is excluded by `exclusion`.
+///
+/// The `exclusion` is either a version (e.g. `3` or `3.8`) or the path to the
+/// executable or a directory containing it, where every component may be a
+/// glob pattern (see [`matches_glob`]).
+fn is_excluded(path: &Path, version: ExactVersion, exclusion: &str) -> bool {
+    if let Ok(requested) = RequestedVersion::from_str(exclusion) {
+        return version.supports(requested);
+    }
+
+    let mut components = path.components();
+    Path::new(exclusion)
+        .components()
+        .all(|pattern| match (pattern, components.next()) {
+            (Component::Normal(pattern), Some(Component::Normal(name))) => {
+                match (pattern.to_str(), name.to_str()) {
+                    (Some(pattern), Some(name)) => matches_glob(pattern, name),
+                    _ => pattern == name,
+                }
+            }
+            (pattern, component) => Some(pattern) == component,
+        })
+}
+
+/// The first of `exclusions` which excludes the executable at `path` for
+/// `version` (see [`is_excluded`]).
+fn exclusion_for<'a>(
+    path: &Path,
+    version: ExactVersion,
+    exclusions: &'a [String],
+) -> Option<&'a str> {
+    exclusions
+        .iter()
+        .map(String::as_str)
+        .find(|exclusion| is_excluded(path, version, exclusion))
+}
+
 fn all_executables_in_paths(
     paths: impl IntoIterator<Item = PathBuf>,
+    exclusions: &[String],
 ) -> HashMap<ExactVersion, PathBuf> {
     let mut executables = HashMap::new();
     paths.into_iter().for_each(|path| {
         ExactVersion::from_path(&path).map_or((), |version| {
-            executables.entry(version).or_insert(path);
+            if let Some(exclusion) = exclusion_for(&path, version, exclusions) {
+                let printable_path = path.display();
+                log::info!("Excluding {printable_path} as it matches `{exclusion}`");
+            } else {
+                executables.entry(version).or_insert(path);
+            }
         })
     });
 
@@ -359,46 +989,179 @@ fn all_executables_in_paths(
     executables
 }
 
-/// Finds all possible Python executables on `PATH`.
+/// Loads the configuration for the functions which do not take one, falling
+/// back to the default configuration if it is invalid.
+fn load_config() -> Config {
+    Config::load().unwrap_or_else(|error| {
+        log::warn!("Using the default configuration: {error}");
+        Config::default()
+    })
+}
+
+/// Finds all possible Python executables on `PATH` (along with the
+/// directories of the configured [`Config::search_path`]).
+///
+/// See [`all_executables_with_config`] for details.
 pub fn all_executables() -> HashMap<ExactVersion, PathBuf> {
+    all_executables_with_config(&load_config())
+}
+
+/// Finds all possible Python executables on `PATH` and in the directories of
+/// [`Config::search_path`], skipping those excluded by
+/// [`Config::search_exclude`].
+pub fn all_executables_with_config(config: &Config) -> HashMap<ExactVersion, PathBuf> {
     log::info!("Checking PATH environment variable");
-    let path_entries = env_path();
-    log::debug!("PATH: {path_entries:?}");
-    let paths = flatten_directories(path_entries);
-    all_executables_in_paths(paths)
+    let directories = search_directories(config);
+    log::debug!("Search directories: {directories:?}");
+    let paths = flatten_directories(directories);
+    all_executables_in_paths(paths, &config.search_exclude)
+}
+
+/// Finds the Python executables which [`all_executables`] skips due to
+/// [`Config::search_exclude`], along with the exclusion which matched each of
+/// them.
+pub fn excluded_executables(config: &Config) -> Vec<(PathBuf, String)> {
+    if config.search_exclude.is_empty() {
+        return Vec::new();
+    }
+
+    flatten_directories(search_directories(config))
+        .filter_map(|path| {
+            let version = ExactVersion::from_path(&path).ok()?;
+            let exclusion = exclusion_for(&path, version, &config.search_exclude)?;
+            Some((path, exclusion.to_string()))
+        })
+        .collect()
+}
+
+/// The directories containing the interpreters installed by the operating
+/// system.
+static SYSTEM_DIRS: [&str; 2] = ["/usr/bin", "/bin"];
+
+/// Whether the executable at `path` for `version` is a pre-release, as
+/// configured via [`Config::prereleases`] or found out by running it when
+/// [`Config::probe_prereleases`] is set.
+fn is_prerelease(version: ExactVersion, path: &Path, config: &Config) -> bool {
+    config.prereleases.contains(&version)
+        || (config.probe_prereleases
+            && ReleaseLevel::probe(path).map_or(false, |level| level != ReleaseLevel::Final))
 }
 
 fn find_executable_in_hashmap(
     requested: RequestedVersion,
     found_executables: &HashMap<ExactVersion, PathBuf>,
+    config: &Config,
 ) -> Option<PathBuf> {
-    let mut iter = found_executables.iter();
-    match requested {
-        RequestedVersion::Any => iter.max(),
-        RequestedVersion::MajorOnly(_) => iter.filter(|pair| pair.0.supports(requested)).max(),
-        RequestedVersion::Exact(_, _) => iter.find(|pair| pair.0.supports(requested)),
+    if let RequestedVersion::Exact(_, _) | RequestedVersion::Prerelease(_, _, _) = requested {
+        return found_executables
+            .iter()
+            .find(|pair| pair.0.supports(requested))
+            .map(|pair| pair.1.clone());
     }
-    .map(|pair| pair.1.clone())
+
+    let mut candidates = found_executables
+        .iter()
+        .filter(|pair| pair.0.supports(requested))
+        .collect::<Vec<_>>();
+    candidates.sort_unstable_by(|a, b| b.0.cmp(a.0));
+    // Pre-releases are only used when nothing else is available, and are
+    // checked lazily (and at most once per executable) as probing an
+    // executable means running it.
+    let prereleases = RefCell::new(HashMap::new());
+    let is_final = |pair: &&(&ExactVersion, &PathBuf)| {
+        !*prereleases
+            .borrow_mut()
+            .entry(pair.1.clone())
+            .or_insert_with(|| is_prerelease(*pair.0, pair.1, config))
+    };
+
+    let system = config
+        .prefer_system
+        .then(|| {
+            candidates
+                .iter()
+                .filter(|pair| {
+                    SYSTEM_DIRS
+                        .iter()
+                        .any(|dir| pair.1.parent() == Some(Path::new(dir)))
+                })
+                .find(is_final)
+        })
+        .flatten();
+    let stable = || {
+        config
+            .stable
+            .and_then(|stable| candidates.iter().find(|pair| *pair.0 == stable))
+    };
+
+    system
+        .or_else(stable)
+        .or_else(|| candidates.iter().find(is_final))
+        .or_else(|| candidates.first())
+        .map(|pair| pair.1.clone())
+}
+
+/// Finds the executable for the pre-release `requested` by running every
+/// executable of its version, as a final release and pre-release of the same
+/// version may both be installed.
+fn find_prerelease(requested: RequestedVersion, config: &Config) -> Option<PathBuf> {
+    let RequestedVersion::Prerelease(_, _, level) = requested else {
+        return None;
+    };
+    flatten_directories(search_directories(config))
+        .filter(|path| {
+            ExactVersion::from_path(path).map_or(false, |version| {
+                version.supports(requested)
+                    && exclusion_for(path, version, &config.search_exclude).is_none()
+            })
+        })
+        .find(|path| ReleaseLevel::probe(path) == Some(level))
 }
 
 /// Attempts to find an executable that satisfies a specified
-/// [`RequestedVersion`] on `PATH`.
+/// [`RequestedVersion`] using the configuration of the Launcher.
+///
+/// See [`find_executable_with_config`] for details.
 pub fn find_executable(requested: RequestedVersion) -> Option<PathBuf> {
-    let found_executables = all_executables();
-    find_executable_in_hashmap(requested, &found_executables)
+    find_executable_with_config(requested, &load_config())
+}
+
+/// Attempts to find an executable that satisfies a specified
+/// [`RequestedVersion`] on `PATH` (or in the directories of
+/// [`Config::search_path`]).
+///
+/// For a [`RequestedVersion::Exact`] version the executable for that version
+/// is used, while for a [`RequestedVersion::Prerelease`] every executable for
+/// the version is run until one of the requested release level is found.
+/// Otherwise the newest version is used, except that:
+///
+/// - An executable installed by the operating system is used first if
+///   [`Config::prefer_system`] is set
+/// - [`Config::stable`] is used next if it satisfies `requested`
+/// - Pre-releases are only used if nothing else is available
+pub fn find_executable_with_config(
+    requested: RequestedVersion,
+    config: &Config,
+) -> Option<PathBuf> {
+    if let RequestedVersion::Prerelease(_, _, _) = requested {
+        return find_prerelease(requested, config);
+    }
+    let found_executables = all_executables_with_config(config);
+    find_executable_in_hashmap(requested, &found_executables, config)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use std::cmp::Ordering;
+    use std::{cmp::Ordering, fs, os::unix::fs::PermissionsExt};
 
     use test_case::test_case;
 
     #[test_case(RequestedVersion::Any => "Python" ; "Any")]
     #[test_case(RequestedVersion::MajorOnly(3) => "Python 3" ; "Major")]
     #[test_case(RequestedVersion::Exact(3, 8) => "Python 3.8" ; "Exact/major.minor")]
+    #[test_case(RequestedVersion::Prerelease(3, 14, ReleaseLevel::Candidate) => "Python 3.14rc" ; "pre-release")]
     fn requestedversion_to_string_tests(requested_version: RequestedVersion) -> String {
         requested_version.to_string()
     }
@@ -413,6 +1176,11 @@ mod tests {
     #[test_case("3.8" => Ok(RequestedVersion::Exact(3, 8)) ; "major.minor")]
     #[test_case("42.13" => Ok(RequestedVersion::Exact(42, 13)) ; "double digit version components")]
     #[test_case("3.6.5" => matches Err(Error::ParseVersionComponentError(_, _)) ; "specifying a micro version is an error")]
+    #[test_case("3.14a" => Ok(RequestedVersion::Prerelease(3, 14, ReleaseLevel::Alpha)) ; "alpha")]
+    #[test_case("3.14b" => Ok(RequestedVersion::Prerelease(3, 14, ReleaseLevel::Beta)) ; "beta")]
+    #[test_case("3.14rc" => Ok(RequestedVersion::Prerelease(3, 14, ReleaseLevel::Candidate)) ; "release candidate")]
+    #[test_case("3.14final" => matches Err(Error::ParseVersionComponentError(_, _)) ; "final is not a pre-release")]
+    #[test_case("3a" => matches Err(Error::ParseVersionComponentError(_, _)) ; "pre-release without a minor version is an error")]
     fn requestedversion_from_str_tests(version_str: &str) -> Result<RequestedVersion> {
         RequestedVersion::from_str(version_str)
     }
@@ -460,6 +1228,95 @@ mod tests {
         assert_eq!(py3_10.cmp(&py3_6), Ordering::Greater);
     }
 
+    fn search_context(available: &[(ComponentSize, ComponentSize)]) -> SearchContext {
+        SearchContext {
+            requested_by: None,
+            directories: vec![PathBuf::from("/usr/bin"), PathBuf::from("/bin")],
+            available: available
+                .iter()
+                .map(|(major, minor)| ExactVersion::new(*major, *minor))
+                .collect(),
+        }
+    }
+
+    #[test_case(RequestedVersion::Any, &[] => None ; "nothing available")]
+    #[test_case(RequestedVersion::Exact(3, 9), &[(3, 12), (3, 11)] => Some("-3.11".to_string()) ; "closest minor version")]
+    #[test_case(RequestedVersion::Exact(3, 11), &[(3, 12), (3, 10)] => Some("-3.12".to_string()) ; "newer on a tie")]
+    #[test_case(RequestedVersion::Exact(4, 0), &[(3, 12), (2, 7)] => Some("-3.12".to_string()) ; "different major version")]
+    #[test_case(RequestedVersion::MajorOnly(4), &[(3, 12), (2, 7)] => Some("-3".to_string()) ; "major-only")]
+    fn searchcontext_suggestion_tests(
+        requested: RequestedVersion,
+        available: &[(ComponentSize, ComponentSize)],
+    ) -> Option<String> {
+        search_context(available).suggestion(requested)
+    }
+
+    #[test]
+    fn no_executable_found_message() {
+        let error = Error::NoExecutableFound(
+            RequestedVersion::Exact(3, 9),
+            search_context(&[(3, 12), (3, 11)]),
+        );
+        assert_eq!(
+            error.to_string(),
+            "No executable found for Python 3.9; available: 3.12, 3.11; did you mean `-3.11`?"
+        );
+
+        let error = Error::NoExecutableFound(
+            RequestedVersion::MajorOnly(3),
+            SearchContext {
+                requested_by: Some("PY_PYTHON".to_string()),
+                ..search_context(&[])
+            },
+        );
+        assert_eq!(
+            error.to_string(),
+            "No executable found for Python 3 (as requested by PY_PYTHON); no Python executables found in /usr/bin, /bin"
+        );
+
+        let error = Error::NoExecutableFound(RequestedVersion::Any, SearchContext::default());
+        assert_eq!(
+            error.to_string(),
+            "No executable found for Python; PATH is empty"
+        );
+        assert_eq!(error.exit_code(), EXIT_NO_EXECUTABLE);
+    }
+
+    #[test_case("plain" => "\"plain\"" ; "no escaping")]
+    #[test_case("say \"hi\"" => r#""say \"hi\"""# ; "quotes")]
+    #[test_case(r"C:\path" => r#""C:\\path""# ; "backslash")]
+    #[test_case("a\nb\tc" => r#""a\nb\tc""# ; "newline and tab")]
+    #[test_case("\u{1b}[0m" => r#""\u001b[0m""# ; "control character")]
+    fn json_string_tests(value: &str) -> String {
+        json_string(value)
+    }
+
+    #[test]
+    fn error_to_json() {
+        let error = Error::NoExecutableFound(
+            RequestedVersion::Exact(3, 9),
+            SearchContext {
+                requested_by: Some("PY_PYTHON".to_string()),
+                ..search_context(&[(3, 12), (3, 11)])
+            },
+        );
+        assert_eq!(
+            error.to_json(64),
+            r#"{"kind":"no_executable_found","message":"No executable found for Python 3.9 (as requested by PY_PYTHON); available: 3.12, 3.11; did you mean `-3.11`?","exit_code":64,"context":{"requested_version":"3.9","requested_by":"PY_PYTHON","directories":["/usr/bin","/bin"],"available":["3.12","3.11"],"suggestion":"-3.11"}}"#
+        );
+
+        let error = Error::NoExecutableFound(RequestedVersion::Any, SearchContext::default());
+        assert_eq!(
+            error.to_json(64),
+            r#"{"kind":"no_executable_found","message":"No executable found for Python; PATH is empty","exit_code":64,"context":{"requested_version":null,"requested_by":null,"directories":[],"available":[],"suggestion":null}}"#
+        );
+
+        assert_eq!(
+            Error::VenvNotFound.to_json(66),
+            r#"{"kind":"venv_not_found","message":"No virtual environment found in the current or parent directories","exit_code":66,"context":{}}"#
+        );
+    }
+
     #[test_case(3, 8 => "3.8" ; "single digits")]
     #[test_case(42, 13 => "42.13" ; "double digits")]
     fn exactversion_to_string_tests(major: ComponentSize, minor: ComponentSize) -> String {
@@ -530,7 +1387,7 @@ mod tests {
             python37_path,
         ];
 
-        let executables = all_executables_in_paths(files);
+        let executables = all_executables_in_paths(files, &[]);
         assert_eq!(executables.len(), 3);
 
         let version = ExactVersion { major, minor };
@@ -538,6 +1395,60 @@ mod tests {
         assert_eq!(executables.get(&version), Some(&PathBuf::from(path)));
     }
 
+    #[test_case("bin", "bin" => true ; "literal")]
+    #[test_case("*", "3.12.1" => true ; "star matches everything")]
+    #[test_case("3.*", "3.12.1" => true ; "star matches a suffix")]
+    #[test_case("*-dev", "3.13-dev" => true ; "star matches a prefix")]
+    #[test_case("3.1?", "3.12" => true ; "question mark matches one character")]
+    #[test_case("3.1?", "3.1" => false ; "question mark requires a character")]
+    #[test_case("3.*", "2.7.18" => false ; "mismatch")]
+    #[test_case("*", ".hidden" => false ; "star skips hidden names")]
+    #[test_case(".*", ".hidden" => true ; "explicit leading dot")]
+    fn matches_glob_tests(pattern: &str, name: &str) -> bool {
+        matches_glob(pattern, name)
+    }
+
+    #[test]
+    fn expand_glob_tests() {
+        let dir = tempfile::tempdir().unwrap();
+        for version in ["3.12.1", "3.11.9", "2.7.18"] {
+            fs::create_dir_all(dir.path().join(version).join("bin")).unwrap();
+        }
+        fs::write(dir.path().join("3.10.0"), "not a directory").unwrap();
+
+        assert_eq!(
+            expand_glob(&dir.path().join("3.*").join("bin")),
+            vec![
+                dir.path().join("3.11.9").join("bin"),
+                dir.path().join("3.12.1").join("bin"),
+            ]
+        );
+        assert_eq!(
+            expand_glob(&dir.path().join("4.*").join("bin")),
+            Vec::<PathBuf>::new()
+        );
+        // A path without a glob pattern is left as-is.
+        let missing = dir.path().join("missing").join("bin");
+        assert_eq!(expand_glob(&missing), vec![missing.clone()]);
+    }
+
+    #[test_case("3.6" => None ; "exact version")]
+    #[test_case("3" => None ; "major version")]
+    #[test_case("2.7" => Some(PathBuf::from("/dir1/python3.6")) ; "other version")]
+    #[test_case("/dir1" => Some(PathBuf::from("/dir2/python3.6")) ; "directory")]
+    #[test_case("/dir1/python3.6" => Some(PathBuf::from("/dir2/python3.6")) ; "executable")]
+    #[test_case("/dir*/python3.?" => None ; "glob")]
+    #[test_case("/dir" => Some(PathBuf::from("/dir1/python3.6")) ; "partial directory name")]
+    fn all_executables_in_paths_exclusions(exclusion: &str) -> Option<PathBuf> {
+        let files = vec![
+            PathBuf::from("/dir1/python3.6"),
+            PathBuf::from("/dir2/python3.6"),
+        ];
+
+        all_executables_in_paths(files, &[exclusion.to_string()])
+            .remove(&ExactVersion { major: 3, minor: 6 })
+    }
+
     #[test_case(RequestedVersion::Any => Some(PathBuf::from("/python3.7")) ; "Any version chooses newest version")]
     #[test_case(RequestedVersion::MajorOnly(42) => None ; "major-only version newer than any options")]
     #[test_case(RequestedVersion::MajorOnly(3) => Some(PathBuf::from("/python3.7")) ; "matching major version chooses newest minor version")]
@@ -546,7 +1457,7 @@ mod tests {
     fn find_executable_in_hashmap_tests(requested_version: RequestedVersion) -> Option<PathBuf> {
         let mut executables = HashMap::new();
         assert_eq!(
-            find_executable_in_hashmap(RequestedVersion::Any, &executables),
+            find_executable_in_hashmap(RequestedVersion::Any, &executables, &Config::default()),
             None
         );
 
@@ -556,6 +1467,135 @@ mod tests {
         let python37_path = PathBuf::from("/python3.7");
         executables.insert(ExactVersion { major: 3, minor: 7 }, python37_path);
 
-        find_executable_in_hashmap(requested_version, &executables)
+        find_executable_in_hashmap(requested_version, &executables, &Config::default())
+    }
+
+    #[test]
+    fn release_level_probe() {
+        let dir = tempfile::tempdir().unwrap();
+        let python = dir.path().join("python3.14");
+        fs::write(&python, "#!/bin/sh\necho beta\n").unwrap();
+        assert_eq!(ReleaseLevel::probe(&python), None);
+
+        fs::set_permissions(&python, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(ReleaseLevel::probe(&python), Some(ReleaseLevel::Beta));
+
+        let version = ExactVersion {
+            major: 3,
+            minor: 14,
+        };
+        assert!(!is_prerelease(version, &python, &Config::default()));
+        let config = Config {
+            probe_prereleases: true,
+            ..Config::default()
+        };
+        assert!(is_prerelease(version, &python, &config));
+    }
+
+    #[test]
+    fn find_executable_in_hashmap_probes_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let runs = dir.path().join("runs");
+        let mut executables = HashMap::new();
+        for (minor, level) in [(13, "final"), (14, "beta")] {
+            let python = dir.path().join(format!("python3.{minor}"));
+            let printable_runs = runs.display();
+            fs::write(
+                &python,
+                format!("#!/bin/sh\necho 3.{minor} >> '{printable_runs}'\necho {level}\n"),
+            )
+            .unwrap();
+            fs::set_permissions(&python, fs::Permissions::from_mode(0o755)).unwrap();
+            executables.insert(ExactVersion { major: 3, minor }, python);
+        }
+        let config = Config {
+            probe_prereleases: true,
+            prefer_system: true,
+            ..Config::default()
+        };
+
+        assert_eq!(
+            find_executable_in_hashmap(RequestedVersion::Any, &executables, &config),
+            Some(dir.path().join("python3.13"))
+        );
+        assert_eq!(fs::read_to_string(runs).unwrap(), "3.14\n3.13\n");
+    }
+
+    #[test]
+    fn find_prerelease_tests() {
+        let dir1 = tempfile::tempdir().unwrap();
+        let dir2 = tempfile::tempdir().unwrap();
+        let final_release = dir1.path().join("python3.14");
+        let candidate = dir2.path().join("python3.14");
+        for (path, level) in [(&final_release, "final"), (&candidate, "candidate")] {
+            fs::write(path, format!("#!/bin/sh\necho {level}\n")).unwrap();
+            fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let config = Config {
+            search_path: vec![
+                dir1.path().display().to_string(),
+                dir2.path().display().to_string(),
+            ],
+            ..Config::default()
+        };
+
+        assert_eq!(
+            find_prerelease(
+                RequestedVersion::Prerelease(3, 14, ReleaseLevel::Candidate),
+                &config
+            ),
+            Some(candidate)
+        );
+        assert_eq!(
+            find_prerelease(
+                RequestedVersion::Prerelease(3, 14, ReleaseLevel::Beta),
+                &config
+            ),
+            None
+        );
+    }
+
+    #[test_case(RequestedVersion::Any, &Config::default() => PathBuf::from("/opt/python3.14") ; "newest by default")]
+    #[test_case(RequestedVersion::Any, &Config { prereleases: vec![ExactVersion { major: 3, minor: 14 }], ..Config::default() } => PathBuf::from("/usr/local/bin/python3.13") ; "pre-release skipped")]
+    #[test_case(RequestedVersion::Exact(3, 14), &Config { prereleases: vec![ExactVersion { major: 3, minor: 14 }], ..Config::default() } => PathBuf::from("/opt/python3.14") ; "pre-release requested exactly")]
+    #[test_case(RequestedVersion::MajorOnly(3), &Config { prereleases: vec![ExactVersion { major: 3, minor: 14 }, ExactVersion { major: 3, minor: 13 }, ExactVersion { major: 3, minor: 12 }], ..Config::default() } => PathBuf::from("/opt/python3.14") ; "only pre-releases available")]
+    #[test_case(RequestedVersion::Any, &Config { stable: Some(ExactVersion { major: 3, minor: 12 }), ..Config::default() } => PathBuf::from("/usr/bin/python3.12") ; "stable series")]
+    #[test_case(RequestedVersion::MajorOnly(2), &Config { stable: Some(ExactVersion { major: 3, minor: 12 }), ..Config::default() } => PathBuf::from("/usr/bin/python2.7") ; "stable series not requested")]
+    #[test_case(RequestedVersion::Any, &Config { stable: Some(ExactVersion { major: 3, minor: 11 }), ..Config::default() } => PathBuf::from("/opt/python3.14") ; "stable series not available")]
+    #[test_case(RequestedVersion::Any, &Config { prefer_system: true, stable: Some(ExactVersion { major: 3, minor: 13 }), ..Config::default() } => PathBuf::from("/usr/bin/python3.12") ; "system interpreter")]
+    #[test_case(RequestedVersion::MajorOnly(2), &Config { prefer_system: true, prereleases: vec![ExactVersion { major: 2, minor: 7 }], ..Config::default() } => PathBuf::from("/usr/bin/python2.7") ; "system pre-release")]
+    fn find_executable_in_hashmap_preferences(
+        requested_version: RequestedVersion,
+        config: &Config,
+    ) -> PathBuf {
+        let executables = HashMap::from([
+            (
+                ExactVersion {
+                    major: 3,
+                    minor: 14,
+                },
+                PathBuf::from("/opt/python3.14"),
+            ),
+            (
+                ExactVersion {
+                    major: 3,
+                    minor: 13,
+                },
+                PathBuf::from("/usr/local/bin/python3.13"),
+            ),
+            (
+                ExactVersion {
+                    major: 3,
+                    minor: 12,
+                },
+                PathBuf::from("/usr/bin/python3.12"),
+            ),
+            (
+                ExactVersion { major: 2, minor: 7 },
+                PathBuf::from("/usr/bin/python2.7"),
+            ),
+        ]);
+
+        find_executable_in_hashmap(requested_version, &executables, config).unwrap()
     }
 }
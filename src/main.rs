@@ -32,15 +32,53 @@
 //! - `PY_PYTHON`: specify what Python version to use by default.
 //! - `PY_PYTHON*`: specify the Python version to use by default for a major
 //!   version (e.g. `PY_PYTHON3` for `-3`).
+//!
+//! # Exit codes
+//!
+//! Once a Python interpreter is executed, its exit code is what is returned.
+//! Otherwise the Launcher exits with one of its own exit codes:
+//!
+//! - `200`: no Python interpreter was found.
+//! - `201`: the activated virtual environment is broken.
+//! - `202`: the configuration is invalid.
+//! - `203`: executing the Python interpreter failed.
+//! - `64` to `78`: other errors, following the conventions of `sysexits.h`.
+//!
+//! `--doctor` exits with `1` if any of its checks fail. When supervising the
+//! Python interpreter (see `PY_LAUNCHER_SUPERVISE`), the Launcher exits the
+//! same way the interpreter did, including being killed by the same signal.
 
-use std::{env, ffi::CString, os::unix::ffi::OsStrExt, path::Path};
+use std::{
+    env,
+    ffi::{c_char, c_int, c_short, CString, OsString},
+    io, iter,
+    mem::MaybeUninit,
+    os::unix::{
+        ffi::{OsStrExt, OsStringExt},
+        process::ExitStatusExt,
+    },
+    path::Path,
+    process::{Command, ExitStatus},
+    ptr,
+    time::{Duration, Instant},
+};
 
 use human_panic::Metadata;
 
-use nix::errno::Errno;
-use nix::unistd;
+use nix::{
+    errno::Errno,
+    sys::{
+        signal::{self, SigSet, SigmaskHow, Signal},
+        wait::{self, WaitPidFlag, WaitStatus},
+    },
+    unistd::{self, Pid},
+};
 
-use python_launcher::cli;
+use python_launcher::{
+    cli, doctor,
+    venv::{self, VirtualEnv},
+    Error,
+};
 
 fn main() {
     human_panic::setup_panic!(Metadata::new(
@@ -66,41 +104,333 @@ fn main() {
         .init()
         .unwrap();
 
-    match cli::Action::from_main(&env::args().collect::<Vec<String>>()) {
+    let mut argv = env::args_os().collect::<Vec<OsString>>();
+    let error_format = cli::ErrorFormat::from_argv(&mut argv)
+        .unwrap_or_else(|error| log_exit(cli::ErrorFormat::Text, error.exit_code(), &error));
+
+    match cli::Action::from_main(&argv) {
         Ok(action) => match action {
             cli::Action::Help(message, executable) => {
                 print!("{message}");
-                run(&executable, &["--help".to_string()])
-                    .map_err(|message| log_exit(Errno::last_raw(), message))
-                    .unwrap()
+                run(&executable, &[OsString::from("--help")])
+                    .unwrap_or_else(|error| log_exit(error_format, error.exit_code(), &error))
+            }
+            cli::Action::List(output)
+            | cli::Action::LauncherVersion(output)
+            | cli::Action::Completions(output)
+            | cli::Action::PromptInfo(output)
+            | cli::Action::ShellInit(output)
+            | cli::Action::Explain(output) => print!("{output}"),
+            cli::Action::Doctor(report) => {
+                print!("{report}");
+                if report.status() == doctor::Status::Fail {
+                    std::process::exit(1);
+                }
+            }
+            cli::Action::CreateVenv {
+                executable,
+                dir,
+                force,
+                upgrade_pip,
+            } => {
+                if let Err(error) = create_venv(&executable, &dir, force, upgrade_pip) {
+                    log_exit(error_format, error.exit_code(), &error)
+                }
             }
-            cli::Action::List(output) => print!("{output}"),
+            cli::Action::RefreshVenv {
+                venv,
+                executable,
+                stale_reason,
+                requirements,
+            } => refresh_venv(&venv, &executable, stale_reason, requirements.as_deref())
+                .unwrap_or_else(|error| log_exit(error_format, error.exit_code(), &error)),
             cli::Action::Execute {
                 executable, args, ..
             } => run(&executable, &args)
-                .map_err(|message| log_exit(Errno::last_raw(), message))
-                .unwrap(),
+                .unwrap_or_else(|error| log_exit(error_format, error.exit_code(), &error)),
+            cli::Action::Supervise {
+                executable,
+                args,
+                post_exit,
+                ..
+            } => {
+                let status = supervise(&executable, &args, post_exit.as_deref())
+                    .unwrap_or_else(|error| log_exit(error_format, error.exit_code(), &error));
+                exit_like(status)
+            }
         },
-        Err(message) => log_exit(message.exit_code(), message),
+        Err(error) => log_exit(error_format, error.exit_code(), &error),
     }
 }
 
-fn log_exit(return_code: i32, message: impl std::error::Error) {
-    log::error!("{message}");
+fn log_exit(error_format: cli::ErrorFormat, return_code: i32, error: &Error) -> ! {
+    match error_format {
+        cli::ErrorFormat::Text => log::error!("{error}"),
+        cli::ErrorFormat::Json => eprintln!("{}", error.to_json(return_code)),
+    }
     std::process::exit(return_code);
 }
 
-fn run(executable: &Path, args: &[String]) -> nix::Result<()> {
+fn create_venv(
+    executable: &Path,
+    dir: &Path,
+    force: bool,
+    upgrade_pip: bool,
+) -> python_launcher::Result<VirtualEnv> {
+    let venv = venv::create(executable, dir, force, upgrade_pip)?;
+    let printable_dir = dir.display();
+    let printable_executable = executable.display();
+    println!("Created a virtual environment in {printable_dir} using {printable_executable}");
+    if let Some(version) = venv.version {
+        println!("Python version: {version}");
+    }
+    if upgrade_pip {
+        println!("Upgraded pip");
+    }
+    Ok(venv)
+}
+
+fn refresh_venv(
+    venv: &VirtualEnv,
+    executable: &Path,
+    stale_reason: Option<String>,
+    requirements: Option<&Path>,
+) -> python_launcher::Result<()> {
+    let printable_root = venv.root.display();
+    let refreshed_venv = match stale_reason {
+        Some(reason) => {
+            println!("Recreating the virtual environment in {printable_root} as {reason}");
+            create_venv(executable, &venv.root, true, false)?
+        }
+        None => {
+            println!("The virtual environment in {printable_root} is up-to-date");
+            venv.clone()
+        }
+    };
+    if let Some(requirements) = requirements {
+        refreshed_venv.install_requirements(requirements)?;
+        let printable_requirements = requirements.display();
+        println!("Installed the requirements from {printable_requirements}");
+    }
+    Ok(())
+}
+
+fn run(executable: &Path, args: &[OsString]) -> Result<(), Error> {
     let printable_executable = executable.display();
     if executable.is_file() {
         log::info!("Executing {printable_executable} with {args:?}");
     } else {
-        log::error!("{printable_executable}: No such file");
-        std::process::exit(1);
+        return Err(Error::ExecFailed(
+            executable.to_path_buf(),
+            "No such file".to_string(),
+        ));
+    }
+    let argv = argv(executable, args)?;
+
+    unistd::execv(&argv[0], &argv)
+        .map(|_| ())
+        .map_err(|errno| Error::ExecFailed(executable.to_path_buf(), errno.desc().to_string()))
+}
+
+/// Converts `bytes` for use by `executable` (e.g. as an argument).
+fn to_cstring(executable: &Path, bytes: &[u8]) -> Result<CString, Error> {
+    CString::new(bytes).map_err(|_| {
+        Error::ExecFailed(
+            executable.to_path_buf(),
+            "An argument contains a NUL byte".to_string(),
+        )
+    })
+}
+
+/// The `argv` for running `executable` with `args`.
+fn argv(executable: &Path, args: &[OsString]) -> Result<Vec<CString>, Error> {
+    iter::once(executable.as_os_str())
+        .chain(args.iter().map(OsString::as_os_str))
+        .map(|arg| to_cstring(executable, arg.as_bytes()))
+        .collect()
+}
+
+/// The signals which are forwarded to the Python interpreter when supervising
+/// it.
+const FORWARDED_SIGNALS: [Signal; 3] = [Signal::SIGINT, Signal::SIGTERM, Signal::SIGWINCH];
+
+/// Whether the Launcher is in the foreground process group of its terminal.
+fn in_foreground() -> bool {
+    unistd::tcgetpgrp(io::stdin()).map_or(false, |pgrp| pgrp == unistd::getpgrp())
+}
+
+/// Runs `executable` as a child process, forwarding signals to it until it
+/// exits, and then runs any `post_exit` command.
+fn supervise(
+    executable: &Path,
+    args: &[OsString],
+    post_exit: Option<&str>,
+) -> Result<ExitStatus, Error> {
+    let printable_executable = executable.display();
+    log::info!("Running {printable_executable} with {args:?} as a child process");
+    let exec_failed = |reason: String| Error::ExecFailed(executable.to_path_buf(), reason);
+
+    // With the signals blocked they are only received via `SigSet::wait()`,
+    // so there is no race between the child exiting and forwarding a signal
+    // to it, and no signal can kill the Launcher (orphaning the child) while
+    // it is being spawned. The child gets the original signal mask back via
+    // `posix_spawn()`.
+    let mut signals = SigSet::empty();
+    for forwarded in FORWARDED_SIGNALS {
+        signals.add(forwarded);
+    }
+    signals.add(Signal::SIGCHLD);
+    let original_mask = signals
+        .thread_swap_mask(SigmaskHow::SIG_BLOCK)
+        .map_err(|errno| exec_failed(errno.desc().to_string()))?;
+
+    let start = Instant::now();
+    let pid = spawn(executable, args, &original_mask).map_err(|error| {
+        let _ = original_mask.thread_set_mask();
+        error
+    })?;
+
+    let status = loop {
+        // SIGCHLD is only pending once even if it was sent multiple times, so
+        // check whether the child exited before waiting for another signal.
+        match wait::waitpid(pid, Some(WaitPidFlag::WNOHANG)) {
+            Ok(WaitStatus::Exited(_, code)) => break ExitStatus::from_raw(code << 8),
+            Ok(WaitStatus::Signaled(_, signal, core_dumped)) => {
+                break ExitStatus::from_raw(signal as i32 | if core_dumped { 0x80 } else { 0 })
+            }
+            Ok(_) => {}
+            Err(errno) => return Err(exec_failed(errno.desc().to_string())),
+        }
+        let received = signals
+            .wait()
+            .map_err(|errno| exec_failed(errno.desc().to_string()))?;
+        if received == Signal::SIGCHLD {
+            continue;
+        } else if received == Signal::SIGINT && in_foreground() {
+            // The terminal sends SIGINT (e.g. from Ctrl-C) to the whole
+            // foreground process group, so the child already received it.
+            log::debug!("Not forwarding {received} as {pid} received it from the terminal");
+        } else {
+            log::debug!("Forwarding {received} to {pid}");
+            // The child may have exited since the signal was received.
+            let _ = signal::kill(pid, received);
+        }
+    };
+    // Otherwise the post-exit command would inherit the signal mask.
+    let _ = original_mask.thread_set_mask();
+    let elapsed = start.elapsed();
+    log::info!("{printable_executable} exited after {elapsed:?} with {status}");
+
+    if let Some(command) = post_exit {
+        run_post_exit(command, status, elapsed);
+    }
+    Ok(status)
+}
+
+/// Spawns `executable` with `args` via `posix_spawn()`, with `mask` as the
+/// child's signal mask and `SIGPIPE` (which Rust ignores) reset to its default
+/// action.
+fn spawn(executable: &Path, args: &[OsString], mask: &SigSet) -> Result<Pid, Error> {
+    let exec_failed = |reason: String| Error::ExecFailed(executable.to_path_buf(), reason);
+    let argv = argv(executable, args)?;
+    let envp = env::vars_os()
+        .map(|(name, value)| {
+            let mut variable = name.into_vec();
+            variable.push(b'=');
+            variable.extend(value.as_bytes());
+            to_cstring(executable, &variable)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let pointers = |strings: &[CString]| {
+        strings
+            .iter()
+            .map(|string| string.as_ptr() as *mut c_char)
+            .chain(iter::once(ptr::null_mut()))
+            .collect::<Vec<_>>()
+    };
+    let (argv_pointers, envp_pointers) = (pointers(&argv), pointers(&envp));
+    let mut default_signals = SigSet::empty();
+    default_signals.add(Signal::SIGPIPE);
+
+    let check = |result: c_int| match result {
+        0 => Ok(()),
+        errno => Err(exec_failed(Errno::from_raw(errno).desc().to_string())),
+    };
+    let mut attr = MaybeUninit::<libc::posix_spawnattr_t>::uninit();
+    let mut pid: libc::pid_t = 0;
+    // SAFETY: `attr` is initialized before use and destroyed afterwards, and
+    // the pointer arrays are null-terminated and outlive the call.
+    unsafe {
+        check(libc::posix_spawnattr_init(attr.as_mut_ptr()))?;
+        let mut attr = attr.assume_init();
+        let result = check(libc::posix_spawnattr_setsigmask(&mut attr, mask.as_ref()))
+            .and_then(|_| {
+                check(libc::posix_spawnattr_setsigdefault(
+                    &mut attr,
+                    default_signals.as_ref(),
+                ))
+            })
+            .and_then(|_| {
+                check(libc::posix_spawnattr_setflags(
+                    &mut attr,
+                    (libc::POSIX_SPAWN_SETSIGMASK | libc::POSIX_SPAWN_SETSIGDEF) as c_short,
+                ))
+            })
+            .and_then(|_| {
+                check(libc::posix_spawn(
+                    &mut pid,
+                    argv[0].as_ptr(),
+                    ptr::null(),
+                    &attr,
+                    argv_pointers.as_ptr(),
+                    envp_pointers.as_ptr(),
+                ))
+            });
+        libc::posix_spawnattr_destroy(&mut attr);
+        result?;
+    }
+    Ok(Pid::from_raw(pid))
+}
+
+/// Runs the post-exit `command` via `sh`.
+///
+/// The command receives the exit status of the interpreter (`128` plus the
+/// signal number if it was killed by a signal, like a shell reports it) via
+/// `PY_LAUNCHER_EXIT_STATUS` and how long it ran for via
+/// `PY_LAUNCHER_ELAPSED_MS`. A failing command is reported but does not change
+/// the Launcher's exit status.
+fn run_post_exit(command: &str, status: ExitStatus, elapsed: Duration) {
+    let exit_status = status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1);
+    log::info!("Running the post-exit command {command:?}");
+    match Command::new("sh")
+        .args(["-c", command])
+        .env("PY_LAUNCHER_EXIT_STATUS", exit_status.to_string())
+        .env("PY_LAUNCHER_ELAPSED_MS", elapsed.as_millis().to_string())
+        .status()
+    {
+        Ok(post_exit_status) if post_exit_status.success() => {}
+        Ok(post_exit_status) => {
+            log::error!("The post-exit command {command:?} failed with {post_exit_status}")
+        }
+        Err(error) => log::error!("Running the post-exit command {command:?} failed: {error}"),
+    }
+}
+
+/// Exits the same way as the child process which exited with `status`.
+fn exit_like(status: ExitStatus) -> ! {
+    if let Some(signal_number) = status.signal() {
+        if let Ok(received) = Signal::try_from(signal_number) {
+            // Being killed by the same signal lets e.g. a shell report how the
+            // interpreter exited.
+            let _ = SigSet::from(received).thread_unblock();
+            let _ = signal::raise(received);
+        }
+        // The signal is ignored, so fall back to what a shell would report.
+        std::process::exit(128 + signal_number);
     }
-    let executable_as_cstring = CString::new(executable.as_os_str().as_bytes()).unwrap();
-    let mut argv = vec![executable_as_cstring.clone()];
-    argv.extend(args.iter().map(|arg| CString::new(arg.as_str()).unwrap()));
 
-    unistd::execv(&executable_as_cstring, &argv).map(|_| ())
+    std::process::exit(status.code().unwrap_or(1))
 }
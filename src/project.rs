@@ -0,0 +1,371 @@
+//! Finding the Python version a project wants
+//!
+//! A project can pin a Python version via a `.python-version` file (as used by
+//! tools like [pyenv](https://github.com/pyenv/pyenv)) or constrain what
+//! versions it supports via the `requires-python` key in its `pyproject.toml`
+//! file. The [`Project`] struct represents whichever was found in a directory.
+
+use std::{
+    fmt::{self, Display},
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{ComponentSize, ExactVersion, RequestedVersion};
+
+/// The file pinning a project's Python version.
+pub static PYTHON_VERSION_FILE: &str = ".python-version";
+
+/// The file containing a project's metadata.
+pub static PYPROJECT_FILE: &str = "pyproject.toml";
+
+/// What a project specifies about its Python version.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProjectVersion {
+    /// The version from a `.python-version` file.
+    Pinned(RequestedVersion),
+    /// The versions allowed by `requires-python` in `pyproject.toml`.
+    Requires(RequiresPython),
+    /// The project does not specify a version that can be understood.
+    Unspecified,
+}
+
+/// A project directory with a file specifying what Python version to use.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Project {
+    /// The file which specifies the Python version.
+    pub file: PathBuf,
+    /// The Python version the project specifies.
+    pub version: ProjectVersion,
+}
+
+impl Project {
+    /// Looks for a project in a directory.
+    ///
+    /// A `.python-version` file takes precedence over a `pyproject.toml` file.
+    /// Returns [`None`] if neither file exists.
+    pub fn from_dir(dir: &Path) -> Option<Self> {
+        let python_version_path = dir.join(PYTHON_VERSION_FILE);
+        if let Ok(contents) = fs::read_to_string(&python_version_path) {
+            let version = parse_python_version_file(&contents)
+                .map_or(ProjectVersion::Unspecified, ProjectVersion::Pinned);
+            return Some(Self {
+                file: python_version_path,
+                version,
+            });
+        }
+
+        let pyproject_path = dir.join(PYPROJECT_FILE);
+        if let Ok(contents) = fs::read_to_string(&pyproject_path) {
+            let version = parse_requires_python(&contents)
+                .and_then(|specifiers| RequiresPython::from_str(&specifiers).ok())
+                .map_or(ProjectVersion::Unspecified, ProjectVersion::Requires);
+            return Some(Self {
+                file: pyproject_path,
+                version,
+            });
+        }
+
+        None
+    }
+}
+
+/// Parses the first version listed in a `.python-version` file.
+///
+/// Versions which don't start with a number (e.g. `pypy3.10` or `system`) are
+/// not supported. Any micro version or suffix is ignored (e.g. `3.12.1` and
+/// `3.13t` are treated as `3.12` and `3.13`, respectively).
+fn parse_python_version_file(contents: &str) -> Option<RequestedVersion> {
+    let line = contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))?;
+    let version_end = line
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(line.len());
+    let mut components = line[..version_end].split('.');
+    let major = components.next()?.parse::<ComponentSize>().ok()?;
+
+    match components.next().map(str::parse::<ComponentSize>) {
+        Some(Ok(minor)) => Some(RequestedVersion::Exact(major, minor)),
+        None => Some(RequestedVersion::MajorOnly(major)),
+        Some(Err(_)) => None,
+    }
+}
+
+/// Finds the value of `requires-python` in the `[project]` table of a
+/// `pyproject.toml` file.
+fn parse_requires_python(contents: &str) -> Option<String> {
+    let mut in_project_table = false;
+
+    for raw_line in contents.lines() {
+        let line = raw_line.trim();
+        if line.starts_with('[') {
+            in_project_table =
+                line.trim_start_matches('[').trim_end_matches(']').trim() == "project";
+        } else if in_project_table {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            if key.trim().trim_matches(|c| c == '"' || c == '\'') != "requires-python" {
+                continue;
+            }
+            let value = value.trim();
+            let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+            let value = &value[1..];
+            return value.find(quote).map(|end| value[..end].to_string());
+        }
+    }
+
+    None
+}
+
+/// A comparison operator of a version specifier.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operator {
+    Compatible,
+    Equal,
+    NotEqual,
+    LessThanEqual,
+    GreaterThanEqual,
+    LessThan,
+    GreaterThan,
+}
+
+/// A single version specifier, e.g. `>=3.10`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Specifier {
+    operator: Operator,
+    components: Vec<ComponentSize>,
+    wildcard: bool,
+}
+
+impl Specifier {
+    /// Checks if a `major.minor` version can satisfy the specifier.
+    ///
+    /// As the micro version of an interpreter isn't known, any micro version
+    /// in the specifier is only considered to the extent it rules out an
+    /// entire `major.minor` version.
+    fn contains(&self, version: ExactVersion) -> bool {
+        let major = self.components[0];
+        let minor = self.components.get(1).copied();
+        let has_micro = self.components.len() > 2;
+        let specified = ExactVersion::new(major, minor.unwrap_or(0));
+
+        match self.operator {
+            Operator::Equal | Operator::NotEqual => {
+                let equal = version.major == major && minor.map_or(true, |m| version.minor == m);
+                if self.operator == Operator::Equal {
+                    equal
+                } else {
+                    // Only a wildcard (e.g. `!=3.11.*`) rules out an entire
+                    // `major.minor` version; `!=3.11` only rules out 3.11.0.
+                    !equal || !self.wildcard || has_micro
+                }
+            }
+            // Even `>3.10` is satisfied by e.g. 3.10.1.
+            Operator::GreaterThanEqual | Operator::GreaterThan => version >= specified,
+            Operator::LessThanEqual => version <= specified,
+            Operator::LessThan if has_micro && self.components[2..].iter().any(|c| *c > 0) => {
+                version <= specified
+            }
+            Operator::LessThan => version < specified,
+            Operator::Compatible if has_micro => {
+                version.major == major && Some(version.minor) == minor
+            }
+            Operator::Compatible => version.major == major && version >= specified,
+        }
+    }
+}
+
+impl FromStr for Specifier {
+    type Err = String;
+
+    fn from_str(specifier: &str) -> Result<Self, Self::Err> {
+        let specifier = specifier.trim();
+        let operators = [
+            ("~=", Operator::Compatible),
+            ("===", Operator::Equal),
+            ("==", Operator::Equal),
+            ("!=", Operator::NotEqual),
+            ("<=", Operator::LessThanEqual),
+            (">=", Operator::GreaterThanEqual),
+            ("<", Operator::LessThan),
+            (">", Operator::GreaterThan),
+        ];
+        let (operator, version) = operators
+            .iter()
+            .find_map(|(symbol, operator)| {
+                specifier
+                    .strip_prefix(symbol)
+                    .map(|version| (*operator, version.trim()))
+            })
+            .ok_or_else(|| format!("'{specifier}' lacks a comparison operator"))?;
+        let (version, wildcard) = match version.strip_suffix(".*") {
+            Some(version) => (version, true),
+            None => (version, false),
+        };
+        let components = version
+            .split('.')
+            .map(|component| {
+                component
+                    .parse::<ComponentSize>()
+                    .map_err(|_| format!("'{specifier}' has an unsupported version"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            operator,
+            components,
+            wildcard,
+        })
+    }
+}
+
+/// The version specifiers of `requires-python`, e.g. `>=3.10,<4`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequiresPython {
+    original: String,
+    specifiers: Vec<Specifier>,
+}
+
+impl RequiresPython {
+    /// Checks if a `major.minor` version satisfies all of the specifiers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    ///
+    /// use python_launcher::{project::RequiresPython, ExactVersion};
+    ///
+    /// let requires_python = RequiresPython::from_str(">=3.10, <3.13").unwrap();
+    ///
+    /// assert!(requires_python.contains(ExactVersion::new(3, 12)));
+    /// assert!(!requires_python.contains(ExactVersion::new(3, 13)));
+    /// ```
+    pub fn contains(&self, version: ExactVersion) -> bool {
+        self.specifiers
+            .iter()
+            .all(|specifier| specifier.contains(version))
+    }
+}
+
+impl FromStr for RequiresPython {
+    type Err = String;
+
+    fn from_str(specifiers: &str) -> Result<Self, Self::Err> {
+        Ok(Self {
+            original: specifiers.trim().to_string(),
+            specifiers: specifiers
+                .split(',')
+                .filter(|specifier| !specifier.trim().is_empty())
+                .map(Specifier::from_str)
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+}
+
+impl Display for RequiresPython {
+    /// Format to the original specifiers, e.g. `>=3.10`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.original)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("3.12\n" => Some(RequestedVersion::Exact(3, 12)) ; "major.minor")]
+    #[test_case("3.12.1\n" => Some(RequestedVersion::Exact(3, 12)) ; "micro version is ignored")]
+    #[test_case("3.13t\n" => Some(RequestedVersion::Exact(3, 13)) ; "suffix is ignored")]
+    #[test_case("3\n" => Some(RequestedVersion::MajorOnly(3)) ; "major-only")]
+    #[test_case("# Comment\n\n3.11\n3.10\n" => Some(RequestedVersion::Exact(3, 11)) ; "first version")]
+    #[test_case("system\n" => None ; "system")]
+    #[test_case("pypy3.10-7.3.12\n" => None ; "alternative implementation")]
+    #[test_case("" => None ; "empty")]
+    fn parse_python_version_file_tests(contents: &str) -> Option<RequestedVersion> {
+        parse_python_version_file(contents)
+    }
+
+    #[test_case("[project]\nrequires-python = \">=3.10\"\n" => Some(">=3.10".to_string()) ; "double quotes")]
+    #[test_case("[project]\nrequires-python='>=3.10' # Comment\n" => Some(">=3.10".to_string()) ; "single quotes and a comment")]
+    #[test_case("[project]\nname = \"spam\"\n\n[tool.spam]\nrequires-python = \">=3.10\"\n" => None ; "different table")]
+    #[test_case("[ project ]\nname = \"spam\"\nrequires-python = \">=3.8, <4\"\n" => Some(">=3.8, <4".to_string()) ; "whitespace in table header")]
+    #[test_case("[project]\nname = \"spam\"\n" => None ; "missing")]
+    fn parse_requires_python_tests(contents: &str) -> Option<String> {
+        parse_requires_python(contents)
+    }
+
+    #[test_case(">=3.10", 3, 10 => true ; "greater than or equal, equal")]
+    #[test_case(">=3.10", 3, 9 => false ; "greater than or equal, less")]
+    #[test_case(">=3.10.2", 3, 10 => true ; "greater than or equal with micro")]
+    #[test_case(">3.10", 3, 10 => true ; "greater than, equal")]
+    #[test_case(">3.10", 3, 9 => false ; "greater than, less")]
+    #[test_case(">3.10.1", 3, 10 => true ; "greater than with micro")]
+    #[test_case("<3.13", 3, 13 => false ; "less than, equal")]
+    #[test_case("<3.13", 3, 12 => true ; "less than, less")]
+    #[test_case("<3.13.1", 3, 13 => true ; "less than with micro")]
+    #[test_case("<4", 3, 13 => true ; "less than major")]
+    #[test_case("<=3.12", 3, 12 => true ; "less than or equal")]
+    #[test_case("==3.12.*", 3, 12 => true ; "equal wildcard")]
+    #[test_case("==3.*", 3, 12 => true ; "equal major wildcard")]
+    #[test_case("==3.12.4", 3, 12 => true ; "equal with micro")]
+    #[test_case("==3.11", 3, 12 => false ; "not equal")]
+    #[test_case("!=3.11.*", 3, 11 => false ; "not equal wildcard")]
+    #[test_case("!=3.11.2", 3, 11 => true ; "not equal with micro")]
+    #[test_case("!=3.11", 3, 11 => true ; "not equal without micro")]
+    #[test_case("!=3.11.2.*", 3, 11 => true ; "not equal wildcard with micro")]
+    #[test_case("!=3.*", 3, 11 => false ; "not equal major wildcard")]
+    #[test_case("~=3.10", 3, 13 => true ; "compatible")]
+    #[test_case("~=3.10", 4, 0 => false ; "compatible, different major")]
+    #[test_case("~=3.10.2", 3, 11 => false ; "compatible with micro")]
+    #[test_case(">=3.9, <3.12", 3, 11 => true ; "multiple specifiers")]
+    #[test_case(">=3.9, <3.12", 3, 12 => false ; "multiple specifiers, excluded")]
+    fn requires_python_contains_tests(
+        specifiers: &str,
+        major: ComponentSize,
+        minor: ComponentSize,
+    ) -> bool {
+        RequiresPython::from_str(specifiers)
+            .unwrap()
+            .contains(ExactVersion::new(major, minor))
+    }
+
+    #[test_case("3.10" ; "missing operator")]
+    #[test_case(">=three" ; "non-numeric version")]
+    fn requires_python_from_str_errors(specifiers: &str) {
+        assert!(RequiresPython::from_str(specifiers).is_err());
+    }
+
+    #[test]
+    fn project_from_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(Project::from_dir(dir.path()), None);
+
+        let pyproject_path = dir.path().join(PYPROJECT_FILE);
+        fs::write(&pyproject_path, "[project]\nrequires-python = \">=3.10\"\n").unwrap();
+        assert_eq!(
+            Project::from_dir(dir.path()),
+            Some(Project {
+                file: pyproject_path,
+                version: ProjectVersion::Requires(RequiresPython::from_str(">=3.10").unwrap())
+            })
+        );
+
+        // `.python-version` takes precedence.
+        let python_version_path = dir.path().join(PYTHON_VERSION_FILE);
+        fs::write(&python_version_path, "3.11\n").unwrap();
+        assert_eq!(
+            Project::from_dir(dir.path()),
+            Some(Project {
+                file: python_version_path,
+                version: ProjectVersion::Pinned(RequestedVersion::Exact(3, 11))
+            })
+        );
+    }
+}
@@ -0,0 +1,303 @@
+//! Keeping the shell's virtual environment in sync with the Launcher
+//!
+//! The hook [`script`] runs `py --shell-init SHELL --hook` whenever the current
+//! directory changes, which prints the commands for the shell to evaluate
+//! (see [`hook`]). The virtual environment found via the same search the
+//! Launcher uses is activated like its `activate` script would (setting
+//! `VIRTUAL_ENV` and prepending its `bin` directory to `PATH`), and is
+//! deactivated once it is no longer found.
+
+use std::{
+    env,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{cli::venv_path_search, completions::Shell, config::Config, venv::VirtualEnv};
+
+/// The environment variable recording the virtual environment activated by
+/// the hook.
+///
+/// It is what distinguishes a virtual environment activated by the hook from
+/// one activated manually, as the latter is left alone.
+pub static SHELL_VENV_ENV_VAR: &str = "PY_LAUNCHER_SHELL_VENV";
+
+/// A change to an environment variable.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+enum EnvChange {
+    Set(&'static str, String),
+    SetPath(Vec<PathBuf>),
+    Unset(&'static str),
+}
+
+/// Determines how to change the environment so that `found` (if any) is the
+/// activated virtual environment.
+///
+/// Nothing changes if a virtual environment was activated other than by the
+/// hook, as it takes precedence for the Launcher as well.
+fn env_changes(
+    found: Option<&VirtualEnv>,
+    virtual_env: Option<&Path>,
+    hook_venv: Option<&Path>,
+    mut path: Vec<PathBuf>,
+) -> Vec<EnvChange> {
+    let activated_manually = virtual_env.is_some() && virtual_env != hook_venv;
+    if activated_manually || found.map(|venv| venv.root.as_path()) == hook_venv {
+        return Vec::new();
+    }
+
+    let mut changes = Vec::new();
+    if let Some(hook_venv) = hook_venv {
+        let bin_dir = hook_venv.join("bin");
+        if let Some(index) = path.iter().position(|entry| *entry == bin_dir) {
+            path.remove(index);
+        }
+        changes.push(EnvChange::Unset("VIRTUAL_ENV"));
+        changes.push(EnvChange::Unset("VIRTUAL_ENV_PROMPT"));
+        changes.push(EnvChange::Unset(SHELL_VENV_ENV_VAR));
+    }
+
+    if let Some(venv) = found {
+        let root = venv.root.display().to_string();
+        let prompt = venv.prompt.clone().unwrap_or_else(|| {
+            venv.root
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default()
+        });
+        path.insert(0, venv.root.join("bin"));
+        changes.push(EnvChange::Set("VIRTUAL_ENV", root.clone()));
+        changes.push(EnvChange::Set("VIRTUAL_ENV_PROMPT", prompt));
+        changes.push(EnvChange::Set(SHELL_VENV_ENV_VAR, root));
+    }
+    changes.push(EnvChange::SetPath(path));
+
+    changes
+}
+
+/// Formats `changes` as commands for `shell` to evaluate.
+fn render_changes(shell: Shell, changes: &[EnvChange]) -> String {
+    let mut commands = String::new();
+    for change in changes {
+        match (shell, change) {
+            (Shell::Fish, EnvChange::Set(name, value)) => {
+                writeln!(commands, "set -gx {name} {};", shell.quote(value)).unwrap()
+            }
+            (Shell::Fish, EnvChange::SetPath(path)) => {
+                let entries = path
+                    .iter()
+                    .map(|entry| shell.quote(&entry.display().to_string()))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                writeln!(commands, "set -gx PATH {entries};").unwrap()
+            }
+            (Shell::Fish, EnvChange::Unset(name)) => writeln!(commands, "set -e {name};").unwrap(),
+            (_, EnvChange::Set(name, value)) => {
+                writeln!(commands, "export {name}={};", shell.quote(value)).unwrap()
+            }
+            (_, EnvChange::SetPath(path)) => {
+                let joined = env::join_paths(path)
+                    .map(|joined| joined.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                writeln!(commands, "export PATH={};", shell.quote(&joined)).unwrap()
+            }
+            (_, EnvChange::Unset(name)) => writeln!(commands, "unset {name};").unwrap(),
+        }
+    }
+
+    commands
+}
+
+/// Prints the commands for `shell` to evaluate so that the virtual
+/// environment found in the current or parent directories is activated.
+///
+/// # Errors
+///
+/// If the virtual environment found fails the ownership and permission
+/// checks, [`crate::Error::UnsafeVenv`] is returned.
+pub fn hook(shell: Shell, config: &Config) -> crate::Result<String> {
+    let found = venv_path_search(config, false)?
+        .and_then(|venv_path| VirtualEnv::from_executable(&venv_path));
+    let virtual_env = env::var_os("VIRTUAL_ENV").map(PathBuf::from);
+    let hook_venv = env::var_os(SHELL_VENV_ENV_VAR).map(PathBuf::from);
+    let path = env::var_os("PATH")
+        .map(|path| env::split_paths(&path).collect())
+        .unwrap_or_default();
+
+    let changes = env_changes(
+        found.as_ref(),
+        virtual_env.as_deref(),
+        hook_venv.as_deref(),
+        path,
+    );
+    Ok(render_changes(shell, &changes))
+}
+
+/// The hook script for `shell`, to be evaluated when the shell starts.
+///
+/// Nushell is not supported, so [`None`] is returned for it.
+pub fn script(shell: Shell) -> Option<String> {
+    let script = match shell {
+        Shell::Bash => {
+            r#"# Generated by `py --shell-init bash`.
+_py_launcher_hook() {
+    local previous_exit_status=$?
+    if [[ "$PWD" != "${_py_launcher_pwd-}" ]]; then
+        _py_launcher_pwd="$PWD"
+        eval "$(command py --shell-init bash --hook)"
+    fi
+    return $previous_exit_status
+}
+if [[ ";${PROMPT_COMMAND-};" != *";_py_launcher_hook;"* ]]; then
+    PROMPT_COMMAND="_py_launcher_hook${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+fi
+"#
+        }
+        Shell::Zsh => {
+            r#"# Generated by `py --shell-init zsh`.
+_py_launcher_hook() {
+    eval "$(command py --shell-init zsh --hook)"
+}
+autoload -Uz add-zsh-hook
+add-zsh-hook chpwd _py_launcher_hook
+_py_launcher_hook
+"#
+        }
+        Shell::Fish => {
+            r#"# Generated by `py --shell-init fish`.
+function _py_launcher_hook --on-variable PWD
+    command py --shell-init fish --hook | source
+end
+_py_launcher_hook
+"#
+        }
+        Shell::Nushell => return None,
+    };
+
+    Some(script.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split_path(path: &str) -> Vec<PathBuf> {
+        env::split_paths(path).collect()
+    }
+
+    fn venv(root: &str, prompt: Option<&str>) -> VirtualEnv {
+        VirtualEnv {
+            root: PathBuf::from(root),
+            home: None,
+            version: None,
+            executable: None,
+            prompt: prompt.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn activate() {
+        let found = venv("/project/.venv", None);
+        assert_eq!(
+            env_changes(Some(&found), None, None, split_path("/usr/bin:/bin")),
+            [
+                EnvChange::Set("VIRTUAL_ENV", "/project/.venv".to_string()),
+                EnvChange::Set("VIRTUAL_ENV_PROMPT", ".venv".to_string()),
+                EnvChange::Set(SHELL_VENV_ENV_VAR, "/project/.venv".to_string()),
+                EnvChange::SetPath(split_path("/project/.venv/bin:/usr/bin:/bin")),
+            ]
+        );
+    }
+
+    #[test]
+    fn deactivate() {
+        let hook_venv = Path::new("/project/.venv");
+        assert_eq!(
+            env_changes(
+                None,
+                Some(hook_venv),
+                Some(hook_venv),
+                split_path("/usr/bin:/project/.venv/bin:/bin:/project/.venv/bin")
+            ),
+            [
+                EnvChange::Unset("VIRTUAL_ENV"),
+                EnvChange::Unset("VIRTUAL_ENV_PROMPT"),
+                EnvChange::Unset(SHELL_VENV_ENV_VAR),
+                EnvChange::SetPath(split_path("/usr/bin:/bin:/project/.venv/bin")),
+            ]
+        );
+    }
+
+    #[test]
+    fn switch() {
+        let found = venv("/other/.venv", Some("other"));
+        let hook_venv = Path::new("/project/.venv");
+        assert_eq!(
+            env_changes(
+                Some(&found),
+                Some(hook_venv),
+                Some(hook_venv),
+                split_path("/project/.venv/bin:/usr/bin")
+            ),
+            [
+                EnvChange::Unset("VIRTUAL_ENV"),
+                EnvChange::Unset("VIRTUAL_ENV_PROMPT"),
+                EnvChange::Unset(SHELL_VENV_ENV_VAR),
+                EnvChange::Set("VIRTUAL_ENV", "/other/.venv".to_string()),
+                EnvChange::Set("VIRTUAL_ENV_PROMPT", "other".to_string()),
+                EnvChange::Set(SHELL_VENV_ENV_VAR, "/other/.venv".to_string()),
+                EnvChange::SetPath(split_path("/other/.venv/bin:/usr/bin")),
+            ]
+        );
+    }
+
+    #[test]
+    fn unchanged() {
+        let found = venv("/project/.venv", None);
+        let hook_venv = Path::new("/project/.venv");
+        let path = split_path("/project/.venv/bin:/usr/bin");
+
+        // Already activated by the hook.
+        assert!(
+            env_changes(Some(&found), Some(hook_venv), Some(hook_venv), path.clone()).is_empty()
+        );
+        // Activated manually.
+        assert!(
+            env_changes(Some(&found), Some(Path::new("/manual")), None, path.clone()).is_empty()
+        );
+        // Nothing to activate.
+        assert!(env_changes(None, None, None, path).is_empty());
+    }
+
+    #[test]
+    fn render_changes_per_shell() {
+        let changes = [
+            EnvChange::Unset("VIRTUAL_ENV_PROMPT"),
+            EnvChange::Set("VIRTUAL_ENV", "/it's/.venv".to_string()),
+            EnvChange::SetPath(split_path("/it's/.venv/bin:/usr/bin")),
+        ];
+
+        assert_eq!(
+            render_changes(Shell::Bash, &changes),
+            "unset VIRTUAL_ENV_PROMPT;\nexport VIRTUAL_ENV='/it'\\''s/.venv';\nexport PATH='/it'\\''s/.venv/bin:/usr/bin';\n"
+        );
+        assert_eq!(
+            render_changes(Shell::Fish, &changes),
+            "set -e VIRTUAL_ENV_PROMPT;\nset -gx VIRTUAL_ENV '/it\\'s/.venv';\nset -gx PATH '/it\\'s/.venv/bin' '/usr/bin';\n"
+        );
+    }
+
+    #[test]
+    fn script_per_shell() {
+        assert!(script(Shell::Bash)
+            .unwrap()
+            .contains("eval \"$(command py --shell-init bash --hook)\""));
+        assert!(script(Shell::Zsh)
+            .unwrap()
+            .contains("add-zsh-hook chpwd _py_launcher_hook"));
+        assert!(script(Shell::Fish)
+            .unwrap()
+            .contains("command py --shell-init fish --hook | source"));
+        assert_eq!(script(Shell::Nushell), None);
+    }
+}
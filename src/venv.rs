@@ -0,0 +1,396 @@
+//! Inspecting virtual environments
+//!
+//! The [`VirtualEnv`] struct represents a virtual environment as described by
+//! its `pyvenv.cfg` file, allowing details like the Python version to be
+//! gathered without executing the interpreter. Virtual environments can be
+//! created via [`create`].
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{ComponentSize, ExactVersion};
+
+/// The name of the configuration file found at the root of every virtual
+/// environment.
+pub static PYVENV_CFG: &str = "pyvenv.cfg";
+
+/// Details about a virtual environment.
+///
+/// Any detail which is not specified by `pyvenv.cfg` (or if the file is
+/// missing) is set to [`None`].
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct VirtualEnv {
+    /// The root directory of the virtual environment.
+    pub root: PathBuf,
+    /// The directory containing the interpreter the virtual environment was
+    /// created from (the `home` key).
+    pub home: Option<PathBuf>,
+    /// The Python version of the virtual environment (the `version` or
+    /// `version_info` key).
+    pub version: Option<ExactVersion>,
+    /// The interpreter the virtual environment was created from (the
+    /// `executable` key).
+    pub executable: Option<PathBuf>,
+    /// The custom prompt for the virtual environment (the `prompt` key).
+    pub prompt: Option<String>,
+}
+
+impl VirtualEnv {
+    /// Constructs a [`VirtualEnv`] from the root directory of a virtual
+    /// environment.
+    ///
+    /// If `pyvenv.cfg` cannot be read then all details beyond the root
+    /// directory are left unset.
+    pub fn from_root(root: &Path) -> Self {
+        let cfg_path = root.join(PYVENV_CFG);
+        let printable_cfg_path = cfg_path.display();
+        log::info!("Reading {printable_cfg_path}");
+        match fs::read_to_string(&cfg_path) {
+            Ok(contents) => Self::parse(root, &contents),
+            Err(error) => {
+                log::debug!("Failed to read {printable_cfg_path}: {error}");
+                Self::parse(root, "")
+            }
+        }
+    }
+
+    /// Constructs a [`VirtualEnv`] from the path to the virtual environment's
+    /// `bin/python` executable.
+    ///
+    /// Returns [`None`] if the path is not nested two directories deep.
+    pub fn from_executable(executable: &Path) -> Option<Self> {
+        executable
+            .parent()
+            .and_then(|bin_dir| bin_dir.parent())
+            .map(Self::from_root)
+    }
+
+    fn parse(root: &Path, contents: &str) -> Self {
+        let mut venv = Self {
+            root: root.to_path_buf(),
+            home: None,
+            version: None,
+            executable: None,
+            prompt: None,
+        };
+
+        // Mirrors how the `site` module parses the file.
+        for line in contents.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_lowercase().as_str() {
+                "home" => venv.home = Some(PathBuf::from(value)),
+                "version" | "version_info" => {
+                    venv.version = venv.version.or_else(|| version_prefix(value))
+                }
+                "executable" => venv.executable = Some(PathBuf::from(value)),
+                "prompt" => venv.prompt = Some(value.trim_matches('\'').to_string()),
+                _ => {}
+            }
+        }
+
+        venv
+    }
+
+    /// The path to the virtual environment's Python executable.
+    pub fn python(&self) -> PathBuf {
+        self.root.join("bin").join("python")
+    }
+
+    /// Explains why the virtual environment is stale, returning [`None`] if
+    /// it is up-to-date.
+    ///
+    /// A virtual environment is stale if its executable or the interpreter it
+    /// was created from no longer exist, or if a `version` was requested and
+    /// the virtual environment's version differs from it.
+    pub fn stale_reason(&self, version: Option<ExactVersion>) -> Option<String> {
+        if !self.python().is_file() {
+            let printable_python = self.python().display().to_string();
+            return Some(format!("{printable_python} no longer exists"));
+        } else if let Some(base_executable) = self.base_executable() {
+            if !base_executable.is_file() {
+                let printable_base_executable = base_executable.display();
+                return Some(format!(
+                    "the interpreter it was created from, {printable_base_executable}, no longer exists"
+                ));
+            }
+        }
+
+        let version = version?;
+        match self.version {
+            Some(venv_version) if venv_version == version => None,
+            Some(venv_version) => Some(format!(
+                "it uses Python {venv_version} instead of Python {version}"
+            )),
+            None => Some(format!("{PYVENV_CFG} does not specify a version")),
+        }
+    }
+
+    /// Installs the requirements file at `requirements` into the virtual
+    /// environment via pip.
+    ///
+    /// # Errors
+    ///
+    /// If pip fails, [`crate::Error::CommandFailed`] is returned.
+    pub fn install_requirements(&self, requirements: &Path) -> crate::Result<()> {
+        run(Command::new(self.python())
+            .args(["-m", "pip", "install", "--requirement"])
+            .arg(requirements))
+    }
+
+    /// The interpreter the virtual environment was created from.
+    ///
+    /// The `executable` key is preferred. Otherwise the `home` directory is
+    /// checked for a `pythonX.Y` executable based on the virtual environment's
+    /// version.
+    pub fn base_executable(&self) -> Option<PathBuf> {
+        self.executable.clone().or_else(|| {
+            let home = self.home.as_ref()?;
+            let version = self.version?;
+            Some(home.join(format!("python{version}")))
+        })
+    }
+}
+
+/// Creates a virtual environment in `dir` via `executable -m venv`.
+///
+/// If `clear` is true then any existing virtual environment in `dir` is
+/// replaced. If `upgrade_pip` is true then pip is upgraded in the new virtual
+/// environment.
+///
+/// # Errors
+///
+/// If `clear` is true and `dir` contains anything other than a virtual
+/// environment, [`crate::Error::NotAVenv`] is returned so that nothing else
+/// is deleted.
+///
+/// If either command fails to run or exits unsuccessfully,
+/// [`crate::Error::CommandFailed`] is returned.
+pub fn create(
+    executable: &Path,
+    dir: &Path,
+    clear: bool,
+    upgrade_pip: bool,
+) -> crate::Result<VirtualEnv> {
+    let mut venv_command = Command::new(executable);
+    venv_command.args(["-m", "venv"]);
+    if clear {
+        let is_empty_dir = dir
+            .read_dir()
+            .map_or(true, |mut entries| entries.next().is_none());
+        if !is_empty_dir && !dir.join(PYVENV_CFG).is_file() {
+            return Err(crate::Error::NotAVenv(dir.to_path_buf()));
+        }
+        venv_command.arg("--clear");
+    }
+    run(venv_command.arg(dir))?;
+
+    let venv = VirtualEnv::from_root(dir);
+    if upgrade_pip {
+        run(Command::new(venv.python()).args(["-m", "pip", "install", "--upgrade", "pip"]))?;
+    }
+
+    Ok(venv)
+}
+
+fn run(command: &mut Command) -> crate::Result<()> {
+    let printable_command = format!("{command:?}");
+    log::info!("Running {printable_command}");
+    match command.status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(crate::Error::CommandFailed(
+            printable_command,
+            status.to_string(),
+        )),
+        Err(error) => Err(crate::Error::CommandFailed(
+            printable_command,
+            error.to_string(),
+        )),
+    }
+}
+
+/// Parses the `major.minor` prefix of a version string like `3.12.1` or
+/// `3.11.4.final.0`.
+fn version_prefix(version_string: &str) -> Option<ExactVersion> {
+    let mut components = version_string.split('.');
+    let major = components.next()?.parse::<ComponentSize>().ok()?;
+    let minor = components.next()?.parse::<ComponentSize>().ok()?;
+    Some(ExactVersion::new(major, minor))
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("3.12.1" => Some(ExactVersion::new(3, 12)) ; "venv format")]
+    #[test_case("3.11.4.final.0" => Some(ExactVersion::new(3, 11)) ; "virtualenv format")]
+    #[test_case("3.13" => Some(ExactVersion::new(3, 13)) ; "major.minor")]
+    #[test_case("3" => None ; "major-only")]
+    #[test_case("three.twelve" => None ; "non-numeric")]
+    fn version_prefix_tests(version_string: &str) -> Option<ExactVersion> {
+        version_prefix(version_string)
+    }
+
+    #[test]
+    fn parse_venv_cfg() {
+        let contents = "home = /usr/bin\n\
+                        include-system-site-packages = false\n\
+                        version = 3.12.1\n\
+                        executable = /usr/bin/python3.12\n\
+                        command = /usr/bin/python3.12 -m venv /proj/.venv\n\
+                        prompt = 'proj'\n";
+        let venv = VirtualEnv::parse(Path::new("/proj/.venv"), contents);
+
+        assert_eq!(venv.root, PathBuf::from("/proj/.venv"));
+        assert_eq!(venv.home, Some(PathBuf::from("/usr/bin")));
+        assert_eq!(venv.version, Some(ExactVersion::new(3, 12)));
+        assert_eq!(venv.executable, Some(PathBuf::from("/usr/bin/python3.12")));
+        assert_eq!(venv.prompt, Some("proj".to_string()));
+        assert_eq!(venv.python(), PathBuf::from("/proj/.venv/bin/python"));
+    }
+
+    #[test]
+    fn parse_virtualenv_cfg() {
+        let contents = "home = /opt/python/bin\n\
+                        implementation = CPython\n\
+                        version_info = 3.11.4.final.0\n\
+                        virtualenv = 20.24.0\n";
+        let venv = VirtualEnv::parse(Path::new("/proj/.venv"), contents);
+
+        assert_eq!(venv.version, Some(ExactVersion::new(3, 11)));
+        assert_eq!(venv.executable, None);
+        assert_eq!(
+            venv.base_executable(),
+            Some(PathBuf::from("/opt/python/bin/python3.11"))
+        );
+    }
+
+    #[test]
+    fn parse_empty_cfg() {
+        let venv = VirtualEnv::parse(Path::new("/proj/.venv"), "");
+
+        assert_eq!(venv.home, None);
+        assert_eq!(venv.version, None);
+        assert_eq!(venv.base_executable(), None);
+    }
+
+    /// Writes a fake Python executable which records its arguments and creates
+    /// a virtual environment for `-m venv`.
+    fn fake_python(dir: &Path) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let python = dir.join("python3.12");
+        let printable_calls = dir.join("calls").display().to_string();
+        fs::write(
+            &python,
+            format!(
+                "#!/bin/sh\n\
+                 echo \"$@\" >> '{printable_calls}'\n\
+                 if [ \"$2\" = venv ]; then\n\
+                     for venv_dir; do :; done\n\
+                     mkdir -p \"$venv_dir/bin\"\n\
+                     printf 'version = 3.12.1\\n' > \"$venv_dir/pyvenv.cfg\"\n\
+                     ln -s \"$0\" \"$venv_dir/bin/python\"\n\
+                 fi\n"
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&python, fs::Permissions::from_mode(0o755)).unwrap();
+        python
+    }
+
+    #[test]
+    fn create_venv() {
+        let dir = tempfile::tempdir().unwrap();
+        let python = fake_python(dir.path());
+        let venv_dir = dir.path().join(".venv");
+
+        let venv = create(&python, &venv_dir, true, true).unwrap();
+
+        assert_eq!(venv.root, venv_dir);
+        assert_eq!(venv.version, Some(ExactVersion::new(3, 12)));
+        let calls = fs::read_to_string(dir.path().join("calls")).unwrap();
+        let printable_venv_dir = venv_dir.display();
+        assert_eq!(
+            calls,
+            format!("-m venv --clear {printable_venv_dir}\n-m pip install --upgrade pip\n")
+        );
+    }
+
+    #[test]
+    fn create_venv_not_a_venv() {
+        let dir = tempfile::tempdir().unwrap();
+        let python = fake_python(dir.path());
+        let src_dir = dir.path().join("src");
+        fs::create_dir(&src_dir).unwrap();
+        fs::write(src_dir.join("main.py"), "").unwrap();
+
+        let result = create(&python, &src_dir, true, false);
+
+        assert_eq!(result.err(), Some(crate::Error::NotAVenv(src_dir.clone())));
+        assert!(src_dir.join("main.py").is_file());
+        assert!(!dir.path().join("calls").exists());
+    }
+
+    #[test]
+    fn create_venv_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = create(&dir.path().join("python3.12"), dir.path(), false, false);
+
+        assert!(matches!(result, Err(crate::Error::CommandFailed(_, _))));
+    }
+
+    #[test]
+    fn stale_reason() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join(".venv");
+        let base_executable = dir.path().join("python3.12");
+        let printable_base_executable = base_executable.display();
+        let contents = format!("version = 3.12.1\nexecutable = {printable_base_executable}\n");
+        let venv = VirtualEnv::parse(&root, &contents);
+
+        assert!(venv
+            .stale_reason(None)
+            .unwrap()
+            .contains("bin/python no longer exists"));
+
+        fs::create_dir_all(root.join("bin")).unwrap();
+        fs::write(venv.python(), "").unwrap();
+        assert!(venv
+            .stale_reason(Some(ExactVersion::new(3, 12)))
+            .unwrap()
+            .contains("the interpreter it was created from"));
+
+        fs::write(&base_executable, "").unwrap();
+        assert_eq!(venv.stale_reason(None), None);
+        assert_eq!(venv.stale_reason(Some(ExactVersion::new(3, 12))), None);
+        assert_eq!(
+            venv.stale_reason(Some(ExactVersion::new(3, 13))),
+            Some("it uses Python 3.12 instead of Python 3.13".to_string())
+        );
+
+        let venv = VirtualEnv::parse(&root, "");
+        assert_eq!(venv.stale_reason(None), None);
+        assert_eq!(
+            venv.stale_reason(Some(ExactVersion::new(3, 12))),
+            Some("pyvenv.cfg does not specify a version".to_string())
+        );
+    }
+
+    #[test]
+    fn from_executable() {
+        let venv = VirtualEnv::from_executable(Path::new("/does/not/exist/.venv/bin/python"));
+
+        assert_eq!(
+            venv.map(|venv| venv.root),
+            Some(PathBuf::from("/does/not/exist/.venv"))
+        );
+    }
+}
@@ -1,17 +1,21 @@
 mod common;
 
 use std::env;
+use std::ffi::OsString;
 use std::fs;
 use std::fs::File;
 use std::io::Write;
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::fs::PermissionsExt;
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 use serial_test::serial;
 
 use python_launcher::cli;
 use python_launcher::cli::Action;
 use python_launcher::Error;
-use python_launcher::RequestedVersion;
+use python_launcher::{ExactVersion, RequestedVersion, SearchContext};
 
 use common::{EnvState, EnvVarState};
 
@@ -42,7 +46,10 @@ fn from_main_help_missing_interpreter() {
         let help = Action::from_main(&[launcher_path.to_string(), (*flag).to_string()]);
         assert_eq!(
             help,
-            Err(crate::Error::NoExecutableFound(RequestedVersion::Any))
+            Err(crate::Error::NoExecutableFound(
+                RequestedVersion::Any,
+                SearchContext::default()
+            ))
         );
     }
 }
@@ -62,6 +69,75 @@ fn from_main_list() {
     }
 }
 
+#[test]
+#[serial]
+fn from_main_list_launcher_path() {
+    let mut env_state = EnvState::new();
+    let opt_dir = tempfile::tempdir().unwrap();
+    let mut pythons = Vec::new();
+    for (version, executable) in [
+        ("3.8.1", "python3.8"),
+        ("3.7.0", "python3.7"),
+        (".hidden", "python3.9"),
+    ] {
+        let bin_dir = opt_dir.path().join(version).join("bin");
+        fs::create_dir_all(&bin_dir).unwrap();
+        pythons.push(common::touch_file(bin_dir.join(executable)));
+    }
+    let pattern = opt_dir.path().join("*").join("bin");
+    env_state
+        .env_vars
+        .change("PY_LAUNCHER_PATH", Some(pattern.to_str().unwrap()));
+
+    match Action::from_main(&["/path/to/py".to_string(), "--list".to_string()]) {
+        Ok(Action::List(output)) => {
+            assert!(output.contains(pythons[0].to_str().unwrap()));
+            assert!(output.contains(pythons[1].to_str().unwrap()));
+            assert!(!output.contains(env_state.python37.to_str().unwrap()));
+            assert!(!output.contains(pythons[2].to_str().unwrap()));
+        }
+        _ => panic!("'--list' did not return Action::List"),
+    }
+
+    env_state
+        .env_vars
+        .change("PY_LAUNCHER_PATH_POSITION", Some("after"));
+    match Action::from_main(&["/path/to/py".to_string(), "--list".to_string()]) {
+        Ok(Action::List(output)) => {
+            assert!(output.contains(pythons[0].to_str().unwrap()));
+            assert!(!output.contains(pythons[1].to_str().unwrap()));
+            assert!(output.contains(env_state.python37.to_str().unwrap()));
+        }
+        _ => panic!("'--list' did not return Action::List"),
+    }
+}
+
+#[test]
+#[serial]
+fn from_main_list_venv() {
+    let working_dir = common::CurrentDir::new();
+    let env_state = EnvState::new();
+    let venv_root = working_dir.dir.path().join(cli::DEFAULT_VENV_DIR);
+    let venv_bin = venv_root.join("bin");
+    fs::create_dir_all(&venv_bin).unwrap();
+    common::touch_file(venv_bin.join("python"));
+    let mut cfg = File::create(venv_root.join("pyvenv.cfg")).unwrap();
+    writeln!(cfg, "home = /path/to/base/bin").unwrap();
+    writeln!(cfg, "version = 3.6.15").unwrap();
+
+    match Action::from_main(&["/path/to/py".to_string(), "--list".to_string()]) {
+        Ok(Action::List(output)) => {
+            let first_line = output.lines().next().unwrap();
+            assert!(first_line.contains("3.6"));
+            assert!(first_line.contains(".venv/bin/python"));
+            assert!(first_line.contains("default"));
+            assert!(first_line.contains("/path/to/base/bin/python3.6"));
+            assert!(output.contains(env_state.python37.to_str().unwrap()));
+        }
+        _ => panic!("'--list' did not return Action::List"),
+    }
+}
+
 #[test]
 #[serial]
 fn from_main_by_flag() {
@@ -82,6 +158,15 @@ fn from_main_by_flag() {
         }
         Ok(Action::Help(_, _)) => panic!("Got back help"),
         Ok(Action::List(_)) => panic!("Got back a list of executables"),
+        Ok(Action::Explain(_)) => panic!("Got back an explanation"),
+        Ok(Action::LauncherVersion(_)) => panic!("Got back the launcher's version"),
+        Ok(Action::Doctor(_)) => panic!("Got back a diagnosis"),
+        Ok(Action::Completions(_)) => panic!("Got back completions"),
+        Ok(Action::PromptInfo(_)) => panic!("Got back prompt info"),
+        Ok(Action::ShellInit(_)) => panic!("Got back a shell script"),
+        Ok(Action::Supervise { .. }) => panic!("Got back an executable to supervise"),
+        Ok(Action::CreateVenv { .. }) => panic!("Got back a virtual environment to create"),
+        Ok(Action::RefreshVenv { .. }) => panic!("Got back a virtual environment to refresh"),
         Err(error) => panic!("No executable found in default case: {error:?}"),
     }
 
@@ -123,7 +208,7 @@ fn from_main_by_flag() {
         }) => {
             assert_eq!(PathBuf::from(launcher_location), launcher_path);
             assert_eq!(executable, env_state.python36);
-            assert_eq!(args, ["-I".to_string()]);
+            assert_eq!(args, ["-I"]);
         }
         _ => panic!("No executable found in `-3.6` case"),
     }
@@ -132,15 +217,25 @@ fn from_main_by_flag() {
 #[test]
 #[serial]
 fn from_main_activated_virtual_env() {
-    let venv_path = "/path/to/venv";
+    let working_dir = common::CurrentDir::new();
+    let venv_path = working_dir.dir.path().join("venv");
+    let mut expected = venv_path.join("bin");
+    fs::create_dir_all(&expected).unwrap();
+    expected.push("python");
     let mut env_state = common::EnvState::new();
-    env_state.env_vars.change("VIRTUAL_ENV", Some(venv_path));
+    env_state
+        .env_vars
+        .change("VIRTUAL_ENV", Some(venv_path.to_str().unwrap()));
+
+    // The executable must exist.
+    assert_eq!(
+        Action::from_main(&["/path/to/py".to_string()]),
+        Err(Error::BrokenVenv(expected.clone()))
+    );
 
+    common::touch_file(expected.clone());
     match Action::from_main(&["/path/to/py".to_string()]) {
         Ok(Action::Execute { executable, .. }) => {
-            let mut expected = PathBuf::from(venv_path);
-            expected.push("bin");
-            expected.push("python");
             assert_eq!(executable, expected);
         }
         _ => panic!("No executable found in `VIRTUAL_ENV` case"),
@@ -216,6 +311,235 @@ fn from_main_default_parent_venv_path() {
     }
 }
 
+#[test]
+#[serial]
+fn from_main_venv_path_stops_at_repository_root() {
+    let working_dir = common::CurrentDir::new();
+    let temp_dir = working_dir.dir.path().to_path_buf();
+    let env_state = common::EnvState::new();
+    let mut venv_path = temp_dir.clone();
+    venv_path.push(cli::DEFAULT_VENV_DIR);
+    venv_path.push("bin");
+    fs::create_dir_all(&venv_path).unwrap();
+    venv_path.push("python");
+    common::touch_file(venv_path);
+
+    let repo = temp_dir.join("repo");
+    fs::create_dir_all(repo.join(".git")).unwrap();
+    let subdir = repo.join("subdir");
+    fs::create_dir(&subdir).unwrap();
+    env::set_current_dir(&subdir).unwrap();
+
+    match Action::from_main(&["/path/to/py".to_string()]) {
+        Ok(Action::Execute { executable, .. }) => {
+            assert_eq!(executable, env_state.python37);
+        }
+        _ => panic!("No executable found outside of the repository case"),
+    }
+}
+
+#[test]
+#[serial]
+fn from_main_venv_path_stops_at_ceiling_dir() {
+    let working_dir = common::CurrentDir::new();
+    let temp_dir = working_dir.dir.path().to_path_buf();
+    let mut env_state = common::EnvState::new();
+    let mut venv_path = temp_dir.clone();
+    venv_path.push(cli::DEFAULT_VENV_DIR);
+    venv_path.push("bin");
+    fs::create_dir_all(&venv_path).unwrap();
+    venv_path.push("python");
+    common::touch_file(venv_path);
+
+    let subdir = temp_dir.join("subdir");
+    fs::create_dir(&subdir).unwrap();
+    env::set_current_dir(&subdir).unwrap();
+    env_state
+        .env_vars
+        .change("PY_LAUNCHER_VENV_CEILING_DIRS", temp_dir.to_str());
+
+    match Action::from_main(&["/path/to/py".to_string()]) {
+        Ok(Action::Execute { executable, .. }) => {
+            assert_eq!(executable, env_state.python37);
+        }
+        _ => panic!("No executable found in ceiling directory case"),
+    }
+}
+
+#[test]
+#[serial]
+fn from_main_unsafe_venv_path() {
+    let working_dir = common::CurrentDir::new();
+    let mut env_state = common::EnvState::new();
+    let shared_dir = working_dir.dir.path().join("shared");
+    let mut venv_path = shared_dir.clone();
+    venv_path.push(cli::DEFAULT_VENV_DIR);
+    venv_path.push("bin");
+    fs::create_dir_all(&venv_path).unwrap();
+    venv_path.push("python");
+    common::touch_file(venv_path.clone());
+    fs::set_permissions(&shared_dir, fs::Permissions::from_mode(0o777)).unwrap();
+    env::set_current_dir(&shared_dir).unwrap();
+
+    match Action::from_main(&["/path/to/py".to_string()]) {
+        Err(Error::UnsafeVenv(executable, reason)) => {
+            assert_eq!(
+                executable.canonicalize().unwrap(),
+                venv_path.canonicalize().unwrap()
+            );
+            assert!(reason.contains("writable by everyone"));
+        }
+        _ => panic!("Unsafe virtual environment was not rejected"),
+    }
+
+    // Specifying a version skips the search entirely.
+    match Action::from_main(&["/path/to/py".to_string(), "-3".to_string()]) {
+        Ok(Action::Execute { executable, .. }) => {
+            assert_eq!(executable, env_state.python37);
+        }
+        _ => panic!("No executable found in unsafe virtual environment case"),
+    }
+
+    env_state
+        .env_vars
+        .change("PY_LAUNCHER_VENV_ALLOW_UNSAFE", Some("true"));
+
+    match Action::from_main(&["/path/to/py".to_string()]) {
+        Ok(Action::Execute { executable, .. }) => {
+            assert_eq!(
+                executable.canonicalize().unwrap(),
+                venv_path.canonicalize().unwrap()
+            );
+        }
+        _ => panic!("PY_LAUNCHER_VENV_ALLOW_UNSAFE did not allow the virtual environment"),
+    }
+}
+
+#[test]
+#[serial]
+fn from_main_venv_dirs() {
+    let working_dir = common::CurrentDir::new();
+    let mut env_state = common::EnvState::new();
+    let mut expected = working_dir.dir.path().to_path_buf();
+    expected.push("venv");
+    expected.push("bin");
+    fs::create_dir_all(&expected).unwrap();
+    expected.push("python");
+    common::touch_file(expected.clone());
+
+    // `venv` is not searched for by default.
+    match Action::from_main(&["/path/to/py".to_string()]) {
+        Ok(Action::Execute { executable, .. }) => {
+            assert_eq!(executable, env_state.python37);
+        }
+        _ => panic!("No executable found in default virtual environment case"),
+    }
+
+    env_state
+        .env_vars
+        .change("PY_LAUNCHER_VENV_DIRS", Some(".venv:venv"));
+
+    match Action::from_main(&["/path/to/py".to_string()]) {
+        Ok(Action::Execute { executable, .. }) => {
+            assert_eq!(
+                executable.canonicalize().unwrap(),
+                expected.canonicalize().unwrap()
+            );
+        }
+        _ => panic!("No executable found in PY_LAUNCHER_VENV_DIRS case"),
+    }
+}
+
+#[test]
+#[serial]
+fn from_main_venv_dirs_config_file() {
+    let working_dir = common::CurrentDir::new();
+    let mut env_state = common::EnvState::new();
+    let mut expected = working_dir.dir.path().to_path_buf();
+    expected.push("env");
+    expected.push("bin");
+    fs::create_dir_all(&expected).unwrap();
+    expected.push("python");
+    common::touch_file(expected.clone());
+
+    let config_dir = tempfile::tempdir().unwrap();
+    let config_path = config_dir.path().join("py.ini");
+    let mut config_file = File::create(&config_path).unwrap();
+    writeln!(config_file, "[venv]").unwrap();
+    writeln!(config_file, "dirs = .venv, env").unwrap();
+    env_state
+        .env_vars
+        .change("PY_LAUNCHER_CONFIG", config_path.to_str());
+
+    match Action::from_main(&["/path/to/py".to_string()]) {
+        Ok(Action::Execute { executable, .. }) => {
+            assert_eq!(
+                executable.canonicalize().unwrap(),
+                expected.canonicalize().unwrap()
+            );
+        }
+        _ => panic!("No executable found in configuration file case"),
+    }
+
+    // The environment variable takes precedence over the configuration file.
+    env_state
+        .env_vars
+        .change("PY_LAUNCHER_VENV_DIRS", Some(".venv"));
+
+    match Action::from_main(&["/path/to/py".to_string()]) {
+        Ok(Action::Execute { executable, .. }) => {
+            assert_eq!(executable, env_state.python37);
+        }
+        _ => panic!("No executable found in PY_LAUNCHER_VENV_DIRS override case"),
+    }
+}
+
+#[test]
+#[serial]
+fn from_main_venv_search_disabled() {
+    let _working_dir = common::CurrentDir::new();
+    let mut env_state = common::EnvState::new();
+    let mut venv_path = PathBuf::new();
+    venv_path.push(cli::DEFAULT_VENV_DIR);
+    venv_path.push("bin");
+    fs::create_dir_all(&venv_path).unwrap();
+    venv_path.push("python");
+    common::touch_file(venv_path);
+    env_state
+        .env_vars
+        .change("PY_LAUNCHER_VENV_SEARCH", Some("false"));
+
+    match Action::from_main(&["/path/to/py".to_string()]) {
+        Ok(Action::Execute { executable, .. }) => {
+            assert_eq!(executable, env_state.python37);
+        }
+        _ => panic!("No executable found when virtual environment search is disabled"),
+    }
+
+    env_state
+        .env_vars
+        .change("PY_LAUNCHER_VENV_SEARCH", Some("sometimes"));
+
+    assert!(matches!(
+        Action::from_main(&["/path/to/py".to_string()]),
+        Err(Error::InvalidConfig(_, _))
+    ));
+}
+
+#[test]
+#[serial]
+fn from_main_missing_config_file() {
+    let mut env_state = common::EnvState::new();
+    env_state
+        .env_vars
+        .change("PY_LAUNCHER_CONFIG", Some("/this/does/not/exist/py.ini"));
+
+    assert!(matches!(
+        Action::from_main(&["/path/to/py".to_string()]),
+        Err(Error::InvalidConfig(_, _))
+    ));
+}
+
 #[test]
 #[serial]
 fn from_main_shebang() {
@@ -236,8 +560,22 @@ fn from_main_shebang() {
         _ => panic!("No executable found in shebang case"),
     }
 
-    // Shebang checking only works for the first argument to avoid accidentally
-    // reading from arguments to Python code itself.
+    // Flags for the interpreter come before the script.
+    match Action::from_main(&[
+        "/path/to/py".to_string(),
+        "-u".to_string(),
+        "-X".to_string(),
+        "dev".to_string(),
+        file_path.to_str().unwrap().to_string(),
+    ]) {
+        Ok(Action::Execute { executable, .. }) => {
+            assert_eq!(executable, env_state.python27);
+        }
+        _ => panic!("No executable found in shebang with flags case"),
+    }
+
+    // Arguments after `-m` belong to the module and so are never read as a
+    // script.
     match Action::from_main(&[
         "/path/to/py".to_string(),
         "-m".to_string(),
@@ -251,6 +589,105 @@ fn from_main_shebang() {
     }
 }
 
+#[test]
+#[serial]
+fn from_main_shebang_args() {
+    let _working_dir = common::CurrentDir::new();
+    let env_state = common::EnvState::new();
+    let temp_dir = tempfile::tempdir().unwrap();
+    let file_path = temp_dir.path().join("shebang.py");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(file, "#!/usr/bin/env -S python3.6 -X dev").unwrap();
+    let file_arg = file_path.to_str().unwrap().to_string();
+
+    match Action::from_main(&[
+        "/path/to/py".to_string(),
+        file_arg.clone(),
+        "--verbose".to_string(),
+    ]) {
+        Ok(Action::Execute {
+            executable, args, ..
+        }) => {
+            assert_eq!(executable, env_state.python36);
+            assert_eq!(args, ["-X", "dev", file_arg.as_str(), "--verbose"]);
+        }
+        _ => panic!("No executable found in shebang case"),
+    }
+}
+
+#[test]
+#[serial]
+fn from_main_shebang_absolute_interpreter() {
+    let _working_dir = common::CurrentDir::new();
+    let env_state = common::EnvState::new();
+    let temp_dir = tempfile::tempdir().unwrap();
+    let interpreter = common::touch_file(temp_dir.path().join("python3.6"));
+    let file_path = temp_dir.path().join("shebang.py");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(file, "#!{} -u", interpreter.display()).unwrap();
+    let argv = [
+        "/path/to/py".to_string(),
+        file_path.to_str().unwrap().to_string(),
+    ];
+
+    // Not executable, so fall back to searching based on the version.
+    match Action::from_main(&argv) {
+        Ok(Action::Execute {
+            executable, args, ..
+        }) => {
+            assert_eq!(executable, env_state.python36);
+            assert_eq!(args[0], "-u");
+        }
+        _ => panic!("No executable found in non-executable shebang case"),
+    }
+
+    fs::set_permissions(&interpreter, fs::Permissions::from_mode(0o755)).unwrap();
+
+    match Action::from_main(&argv) {
+        Ok(Action::Execute {
+            executable, args, ..
+        }) => {
+            assert_eq!(executable, interpreter);
+            assert_eq!(args[0], "-u");
+        }
+        _ => panic!("No executable found in absolute shebang case"),
+    }
+}
+
+#[test]
+#[serial]
+fn from_main_shebang_absolute_interpreter_over_venv() {
+    let _working_dir = common::CurrentDir::new();
+    let mut env_state = common::EnvState::new();
+    let venv_dir = tempfile::tempdir().unwrap();
+    let bin_dir = venv_dir.path().join("bin");
+    fs::create_dir(&bin_dir).unwrap();
+    let venv_python = common::touch_file(bin_dir.join("python"));
+    fs::set_permissions(&venv_python, fs::Permissions::from_mode(0o755)).unwrap();
+    env_state
+        .env_vars
+        .change("VIRTUAL_ENV", Some(venv_dir.path().to_str().unwrap()));
+    let temp_dir = tempfile::tempdir().unwrap();
+    let interpreter = common::touch_file(temp_dir.path().join("python3.6"));
+    fs::set_permissions(&interpreter, fs::Permissions::from_mode(0o755)).unwrap();
+    let file_path = temp_dir.path().join("shebang.py");
+    let mut file = File::create(&file_path).unwrap();
+    writeln!(file, "#!{} -u", interpreter.display()).unwrap();
+
+    match Action::from_main(&[
+        "/path/to/py".to_string(),
+        file_path.to_str().unwrap().to_string(),
+    ]) {
+        Ok(Action::Execute {
+            executable, args, ..
+        }) => {
+            assert_eq!(executable, interpreter);
+            assert_eq!(args[0], "-u");
+        }
+        result => panic!("Unexpected result: {result:?}"),
+    }
+}
+
 #[test]
 #[serial]
 fn from_main_env_var() {
@@ -297,9 +734,887 @@ fn from_main_env_var() {
 #[test]
 #[serial]
 fn from_main_no_executable_found() {
-    let _env_state = common::EnvState::new();
+    let mut env_state = common::EnvState::new();
+    let context = SearchContext::from_path(None);
+    assert_eq!(
+        context.available,
+        vec![
+            ExactVersion::new(3, 7),
+            ExactVersion::new(3, 6),
+            ExactVersion::new(2, 7)
+        ]
+    );
+    assert_eq!(context.directories.len(), 2);
     assert_eq!(
         Action::from_main(&["/path/to/py".to_string(), "-42.13".to_string()]),
-        Err(Error::NoExecutableFound(RequestedVersion::Exact(42, 13)))
+        Err(Error::NoExecutableFound(
+            RequestedVersion::Exact(42, 13),
+            context.clone()
+        ))
+    );
+
+    env_state.env_vars.change("PY_PYTHON", Some("3.8"));
+    match Action::from_main(&["/path/to/py".to_string()]) {
+        Err(error) => assert_eq!(
+            error.to_string(),
+            "No executable found for Python 3.8 (as requested by PY_PYTHON); available: 3.7, 3.6, 2.7; did you mean `-3.7`?"
+        ),
+        result => panic!("Unexpected result: {result:?}"),
+    }
+}
+
+#[test]
+#[serial]
+fn from_main_non_utf8() {
+    let working_dir = common::CurrentDir::new();
+    let env_state = common::EnvState::new();
+    let command = OsString::from_vec(b"print('caf\xe9')".to_vec());
+
+    match Action::from_main(&[
+        OsString::from("/path/to/py"),
+        OsString::from("-c"),
+        command.clone(),
+    ]) {
+        Ok(Action::Execute {
+            executable, args, ..
+        }) => {
+            assert_eq!(executable, env_state.python37);
+            assert_eq!(args, [OsString::from("-c"), command]);
+        }
+        result => panic!("Unexpected result for a non-UTF-8 command: {result:?}"),
+    }
+
+    // A script whose name uses a legacy encoding still has its shebang read.
+    let script = OsString::from_vec(b"caf\xe9.py".to_vec());
+    fs::write(
+        working_dir.dir.path().join(&script),
+        "#! /usr/bin/env python3.6\n",
+    )
+    .unwrap();
+    match Action::from_main(&[OsString::from("/path/to/py"), script.clone()]) {
+        Ok(Action::Execute {
+            executable, args, ..
+        }) => {
+            assert_eq!(executable, env_state.python36);
+            assert_eq!(args, [script.as_os_str()]);
+        }
+        result => panic!("Unexpected result for a non-UTF-8 script: {result:?}"),
+    }
+
+    // The Launcher's own arguments must be UTF-8.
+    assert_eq!(
+        Action::from_main(&[
+            OsString::from("/path/to/py"),
+            OsString::from("--venv"),
+            script,
+        ]),
+        Err(Error::UnrecognizedArgument(
+            PathBuf::from("/path/to/py"),
+            "caf\u{FFFD}.py".to_string()
+        ))
+    );
+}
+
+#[test]
+#[serial]
+fn from_main_module_project() {
+    let working_dir = common::CurrentDir::new();
+    let env_state = common::EnvState::new();
+    let subdir = working_dir.dir.path().join("src");
+    fs::create_dir(&subdir).unwrap();
+    env::set_current_dir(&subdir).unwrap();
+    let module_argv = ["/path/to/py", "-m", "pytest"].map(OsString::from);
+
+    // No project, so the newest version is used.
+    match Action::from_main(&module_argv) {
+        Ok(Action::Execute { executable, .. }) => assert_eq!(executable, env_state.python37),
+        _ => panic!("No executable found in the no-project case"),
+    }
+
+    fs::write(
+        working_dir.dir.path().join("pyproject.toml"),
+        "[project]\nname = \"spam\"\nrequires-python = \">=3.6, <3.7\"\n",
+    )
+    .unwrap();
+    match Action::from_main(&module_argv) {
+        Ok(Action::Execute {
+            executable, args, ..
+        }) => {
+            assert_eq!(executable, env_state.python36);
+            assert_eq!(args, module_argv[1..]);
+        }
+        _ => panic!("No executable found in the `requires-python` case"),
+    }
+
+    // No interpreter satisfying `requires-python` is an error instead of
+    // ignoring the project.
+    let pyproject_path = working_dir.dir.path().join("pyproject.toml");
+    fs::write(
+        &pyproject_path,
+        "[project]\nname = \"spam\"\nrequires-python = \">=3.8\"\n",
+    )
+    .unwrap();
+    match Action::from_main(&module_argv) {
+        Err(Error::NoExecutableFound(RequestedVersion::Any, context)) => assert_eq!(
+            context.requested_by,
+            Some(pyproject_path.display().to_string())
+        ),
+        result => panic!("Unexpected result for the unsatisfiable case: {result:?}"),
+    }
+
+    // `.python-version` takes precedence over `pyproject.toml`.
+    fs::write(working_dir.dir.path().join(".python-version"), "2.7.18\n").unwrap();
+    match Action::from_main(&module_argv) {
+        Ok(Action::Execute { executable, .. }) => assert_eq!(executable, env_state.python27),
+        _ => panic!("No executable found in the `.python-version` case"),
+    }
+
+    // Only modules use the project's version.
+    match Action::from_main(&["/path/to/py", "-c", "pass"].map(String::from)) {
+        Ok(Action::Execute { executable, .. }) => assert_eq!(executable, env_state.python37),
+        _ => panic!("No executable found in the `-c` case"),
+    }
+}
+
+#[test]
+#[serial]
+fn from_main_explain() {
+    let working_dir = common::CurrentDir::new();
+    let env_state = common::EnvState::new();
+    fs::write(working_dir.dir.path().join(".python-version"), "3.6\n").unwrap();
+
+    match Action::from_main(&["/path/to/py", "--explain", "-m", "pytest"].map(String::from)) {
+        Ok(Action::Explain(explanation)) => {
+            let printable_python36 = env_state.python36.display();
+            assert!(explanation.contains(&format!("Executable: {printable_python36}")));
+            assert!(explanation.contains(r#"Arguments: ["-m", "pytest"]"#));
+            assert!(explanation.contains(".python-version, which requests Python 3.6"));
+        }
+        _ => panic!("No explanation for the module case"),
+    }
+
+    match Action::from_main(&["/path/to/py", "--explain", "-2"].map(String::from)) {
+        Ok(Action::Explain(explanation)) => {
+            let printable_python27 = env_state.python27.display();
+            assert!(explanation.contains(&format!("Executable: {printable_python27}")));
+            assert!(explanation.contains("Arguments: []"));
+            assert!(explanation.contains("1. Python 2 was requested via -2"));
+        }
+        _ => panic!("No explanation for the version flag case"),
+    }
+}
+
+#[test]
+#[serial]
+fn from_main_explain_exclusions() {
+    let mut env_state = common::EnvState::new();
+    let dir1 = env_state.python36.parent().unwrap().to_path_buf();
+    let dir2_python36 = env_state.python37.with_file_name("python3.6");
+    let exclusions = format!("{}:2", dir1.display());
+    env_state
+        .env_vars
+        .change("PY_LAUNCHER_EXCLUDE", Some(&exclusions));
+
+    match Action::from_main(&["/path/to/py", "--explain", "-3.6"].map(String::from)) {
+        Ok(Action::Explain(explanation)) => {
+            let printable_python36 = env_state.python36.display();
+            let printable_python27 = env_state.python27.display();
+            let printable_dir1 = dir1.display();
+            let printable_dir2_python36 = dir2_python36.display();
+            assert!(explanation.contains(&format!("Executable: {printable_dir2_python36}")));
+            assert!(explanation.contains(&format!(
+                "{printable_python36} is excluded as it matches \"{printable_dir1}\""
+            )));
+            assert!(explanation.contains(&format!(
+                "{printable_python27} is excluded as it matches \"{printable_dir1}\""
+            )));
+        }
+        _ => panic!("No explanation with exclusions"),
+    }
+
+    match Action::from_main(&["/path/to/py".to_string(), "--list".to_string()]) {
+        Ok(Action::List(output)) => {
+            assert!(!output.contains(env_state.python36.to_str().unwrap()));
+            assert!(!output.contains(env_state.python27.to_str().unwrap()));
+            assert!(output.contains(dir2_python36.to_str().unwrap()));
+        }
+        _ => panic!("'--list' did not return Action::List"),
+    }
+
+    assert!(matches!(
+        Action::from_main(&["/path/to/py", "-2"].map(String::from)),
+        Err(Error::NoExecutableFound(..))
+    ));
+}
+
+#[test]
+#[serial]
+fn from_main_preferences() {
+    let mut env_state = common::EnvState::new();
+    env_state
+        .env_vars
+        .change("PY_LAUNCHER_PRERELEASES", Some("3.7"));
+
+    match Action::from_main(&["/path/to/py", "--explain"].map(String::from)) {
+        Ok(Action::Explain(explanation)) => {
+            let printable_python36 = env_state.python36.display();
+            assert!(explanation.contains(&format!("Executable: {printable_python36}")));
+            assert!(explanation.contains(&format!(
+                "The preferred executable on PATH for Python is {printable_python36}"
+            )));
+        }
+        _ => panic!("No explanation with a pre-release"),
+    }
+    assert_eq!(
+        Action::from_main(&["/path/to/py", "-3.7"].map(String::from)),
+        Ok(Action::Execute {
+            launcher_path: PathBuf::from("/path/to/py"),
+            executable: env_state.python37.clone(),
+            args: Vec::new(),
+        })
+    );
+
+    env_state.env_vars.change("PY_LAUNCHER_STABLE", Some("2.7"));
+    assert_eq!(
+        Action::from_main(&["/path/to/py"].map(String::from)),
+        Ok(Action::Execute {
+            launcher_path: PathBuf::from("/path/to/py"),
+            executable: env_state.python27.clone(),
+            args: Vec::new(),
+        })
+    );
+}
+
+#[test]
+#[serial]
+fn from_main_supervise() {
+    let mut env_state = common::EnvState::new();
+    env_state
+        .env_vars
+        .change("PY_LAUNCHER_SUPERVISE", Some("true"));
+
+    assert_eq!(
+        Action::from_main(&["/path/to/py", "-c", "pass"].map(String::from)),
+        Ok(Action::Supervise {
+            launcher_path: PathBuf::from("/path/to/py"),
+            executable: env_state.python37.clone(),
+            args: vec!["-c".into(), "pass".into()],
+            post_exit: None,
+        })
+    );
+
+    // A post-exit command implies supervision.
+    env_state.env_vars.change("PY_LAUNCHER_SUPERVISE", None);
+    env_state
+        .env_vars
+        .change("PY_LAUNCHER_POST_EXIT", Some("echo done"));
+    assert_eq!(
+        Action::from_main(&["/path/to/py", "-3.6"].map(String::from)),
+        Ok(Action::Supervise {
+            launcher_path: PathBuf::from("/path/to/py"),
+            executable: env_state.python36.clone(),
+            args: Vec::new(),
+            post_exit: Some("echo done".to_string()),
+        })
+    );
+}
+
+#[test]
+#[serial]
+fn from_main_create_venv() {
+    let working_dir = common::CurrentDir::new();
+    let env_state = common::EnvState::new();
+    let venv_dir = working_dir.dir.path().join(".venv");
+
+    match Action::from_main(&["/path/to/py", "--venv"].map(String::from)) {
+        Ok(Action::CreateVenv {
+            executable,
+            dir,
+            force,
+            upgrade_pip,
+        }) => {
+            assert_eq!(executable, env_state.python37);
+            assert_eq!(dir, PathBuf::from(".venv"));
+            assert!(!force);
+            assert!(!upgrade_pip);
+        }
+        result => panic!("Unexpected result for the default case: {result:?}"),
+    }
+
+    // The project's version is used, but not an existing virtual environment.
+    fs::write(working_dir.dir.path().join(".python-version"), "3.6\n").unwrap();
+    fs::create_dir_all(venv_dir.join("bin")).unwrap();
+    common::touch_file(venv_dir.join("bin").join("python"));
+    common::touch_file(venv_dir.join("pyvenv.cfg"));
+    match Action::from_main(&["/path/to/py", "--venv", "--upgrade-pip", "env"].map(String::from)) {
+        Ok(Action::CreateVenv {
+            executable,
+            dir,
+            upgrade_pip,
+            ..
+        }) => {
+            assert_eq!(executable, env_state.python36);
+            assert_eq!(dir, PathBuf::from("env"));
+            assert!(upgrade_pip);
+        }
+        result => panic!("Unexpected result for the project case: {result:?}"),
+    }
+
+    // A version flag takes precedence over the project.
+    match Action::from_main(&["/path/to/py", "--venv", "-2", "--force"].map(String::from)) {
+        Ok(Action::CreateVenv {
+            executable, force, ..
+        }) => {
+            assert_eq!(executable, env_state.python27);
+            assert!(force);
+        }
+        result => panic!("Unexpected result for the version flag case: {result:?}"),
+    }
+}
+
+#[test]
+#[serial]
+fn from_main_create_venv_errors() {
+    let working_dir = common::CurrentDir::new();
+    let _env_state = common::EnvState::new();
+    let venv_dir = working_dir.dir.path().join(".venv");
+    fs::create_dir(&venv_dir).unwrap();
+
+    // An empty directory is fine to use.
+    assert!(matches!(
+        Action::from_main(&["/path/to/py", "--venv"].map(String::from)),
+        Ok(Action::CreateVenv { .. })
+    ));
+
+    common::touch_file(venv_dir.join("pyvenv.cfg"));
+    assert_eq!(
+        Action::from_main(&["/path/to/py", "--venv"].map(String::from)),
+        Err(Error::VenvExists(PathBuf::from(".venv")))
+    );
+
+    // Only a virtual environment is replaced, even with `--force`.
+    let src_dir = working_dir.dir.path().join("src");
+    fs::create_dir(&src_dir).unwrap();
+    common::touch_file(src_dir.join("main.py"));
+    assert_eq!(
+        Action::from_main(&["/path/to/py", "--venv", "--force", "src"].map(String::from)),
+        Err(Error::NotAVenv(PathBuf::from("src")))
+    );
+
+    assert_eq!(
+        Action::from_main(&["/path/to/py", "--venv", "--clear"].map(String::from)),
+        Err(Error::UnrecognizedArgument(
+            PathBuf::from("/path/to/py"),
+            "--clear".to_string()
+        ))
+    );
+    assert_eq!(
+        Action::from_main(&["/path/to/py", "--venv", "env", "venv"].map(String::from)),
+        Err(Error::UnrecognizedArgument(
+            PathBuf::from("/path/to/py"),
+            "venv".to_string()
+        ))
+    );
+}
+
+#[test]
+#[serial]
+fn from_main_refresh_venv() {
+    let working_dir = common::CurrentDir::new();
+    let env_state = common::EnvState::new();
+    let refresh_argv = ["/path/to/py", "--refresh-venv"].map(String::from);
+
+    assert_eq!(Action::from_main(&refresh_argv), Err(Error::VenvNotFound));
+
+    // The executable of a virtual environment whose base interpreter was
+    // uninstalled is a dangling symlink.
+    let venv_root = working_dir.dir.path().join(".venv");
+    let bin_dir = venv_root.join("bin");
+    fs::create_dir_all(&bin_dir).unwrap();
+    std::os::unix::fs::symlink(
+        working_dir.dir.path().join("python3.5"),
+        bin_dir.join("python"),
+    )
+    .unwrap();
+    fs::write(venv_root.join("pyvenv.cfg"), "version = 3.5.10\n").unwrap();
+    match Action::from_main(&refresh_argv) {
+        Ok(Action::RefreshVenv {
+            venv,
+            executable,
+            stale_reason,
+            requirements,
+        }) => {
+            assert_eq!(
+                venv.root.canonicalize().unwrap(),
+                venv_root.canonicalize().unwrap()
+            );
+            assert_eq!(executable, env_state.python37);
+            assert!(stale_reason.unwrap().ends_with("no longer exists"));
+            assert_eq!(requirements, None);
+        }
+        result => panic!("Unexpected result for the broken case: {result:?}"),
+    }
+
+    fs::remove_file(bin_dir.join("python")).unwrap();
+    common::touch_file(bin_dir.join("python"));
+    fs::write(venv_root.join("pyvenv.cfg"), "version = 3.6.15\n").unwrap();
+    // A newer Python alone does not make a working virtual environment stale.
+    match Action::from_main(
+        &["/path/to/py", "--refresh-venv", "-r", "requirements.txt"].map(String::from),
+    ) {
+        Ok(Action::RefreshVenv {
+            stale_reason,
+            requirements,
+            ..
+        }) => {
+            assert_eq!(stale_reason, None);
+            assert_eq!(requirements, Some(PathBuf::from("requirements.txt")));
+        }
+        result => panic!("Unexpected result for the unrequested case: {result:?}"),
+    }
+
+    match Action::from_main(
+        &[
+            "/path/to/py",
+            "--refresh-venv",
+            "-3",
+            "-r",
+            "requirements.txt",
+        ]
+        .map(String::from),
+    ) {
+        Ok(Action::RefreshVenv {
+            stale_reason,
+            requirements,
+            ..
+        }) => {
+            assert_eq!(
+                stale_reason,
+                Some("it uses Python 3.6 instead of Python 3.7".to_string())
+            );
+            assert_eq!(requirements, Some(PathBuf::from("requirements.txt")));
+        }
+        result => panic!("Unexpected result for the stale case: {result:?}"),
+    }
+
+    match Action::from_main(&["/path/to/py", "--refresh-venv", "-3.6"].map(String::from)) {
+        Ok(Action::RefreshVenv {
+            executable,
+            stale_reason,
+            ..
+        }) => {
+            assert_eq!(executable, env_state.python36);
+            assert_eq!(stale_reason, None);
+        }
+        result => panic!("Unexpected result for the up-to-date case: {result:?}"),
+    }
+
+    fs::write(working_dir.dir.path().join(".python-version"), "3.7\n").unwrap();
+    match Action::from_main(&refresh_argv) {
+        Ok(Action::RefreshVenv { stale_reason, .. }) => assert_eq!(
+            stale_reason,
+            Some("it uses Python 3.6 instead of Python 3.7".to_string())
+        ),
+        result => panic!("Unexpected result for the project case: {result:?}"),
+    }
+
+    assert_eq!(
+        Action::from_main(&["/path/to/py", "--refresh-venv", "--requirements"].map(String::from)),
+        Err(Error::UnrecognizedArgument(
+            PathBuf::from("/path/to/py"),
+            "--requirements".to_string()
+        ))
+    );
+}
+
+#[test]
+#[serial]
+fn error_format_from_argv() {
+    let mut env_state = common::EnvVarState::empty();
+    env_state.change(cli::ERROR_FORMAT_ENV_VAR, None);
+    let mut argv = ["/path/to/py", "--error-format=json", "-3"]
+        .map(String::from)
+        .to_vec();
+
+    assert_eq!(
+        cli::ErrorFormat::from_argv(&mut argv),
+        Ok(cli::ErrorFormat::Json)
+    );
+    assert_eq!(argv, ["/path/to/py", "-3"].map(String::from));
+
+    // Only the first argument is considered.
+    let mut argv = ["/path/to/py", "-3", "--error-format=json"]
+        .map(String::from)
+        .to_vec();
+    assert_eq!(
+        cli::ErrorFormat::from_argv(&mut argv),
+        Ok(cli::ErrorFormat::Text)
+    );
+    assert_eq!(argv.len(), 3);
+
+    env_state.change(cli::ERROR_FORMAT_ENV_VAR, Some("json"));
+    assert_eq!(
+        cli::ErrorFormat::from_argv(&mut argv),
+        Ok(cli::ErrorFormat::Json)
+    );
+    let mut text_argv = ["/path/to/py", "--error-format=text"]
+        .map(String::from)
+        .to_vec();
+    assert_eq!(
+        cli::ErrorFormat::from_argv(&mut text_argv),
+        Ok(cli::ErrorFormat::Text)
+    );
+
+    env_state.change(cli::ERROR_FORMAT_ENV_VAR, Some("yaml"));
+    assert!(matches!(
+        cli::ErrorFormat::from_argv(&mut argv),
+        Err(Error::InvalidConfig(_, _))
+    ));
+    let mut bad_argv = ["/path/to/py", "--error-format=yaml"]
+        .map(String::from)
+        .to_vec();
+    assert_eq!(
+        cli::ErrorFormat::from_argv(&mut bad_argv),
+        Err(Error::UnrecognizedArgument(
+            PathBuf::from("/path/to/py"),
+            "--error-format=yaml".to_string()
+        ))
+    );
+}
+
+#[test]
+#[serial]
+fn from_main_launcher_version() {
+    let _working_dir = common::CurrentDir::new();
+    let mut env_state = common::EnvState::new();
+
+    match Action::from_main(&["/path/to/py", "--launcher-version"].map(String::from)) {
+        Ok(Action::LauncherVersion(message)) => {
+            assert!(message.starts_with(&format!(
+                "Python Launcher for Unix {}\n",
+                env!("CARGO_PKG_VERSION")
+            )));
+            let printable_python37 = env_state.python37.display();
+            assert!(message.contains(&format!("Default interpreter: {printable_python37}\n")));
+        }
+        result => panic!("Unexpected result: {result:?}"),
+    }
+
+    let config_dir = tempfile::tempdir().unwrap();
+    let config_path = config_dir.path().join("py.ini");
+    fs::write(&config_path, "").unwrap();
+    env_state
+        .env_vars
+        .change("PY_LAUNCHER_CONFIG", Some(config_path.to_str().unwrap()));
+    env_state.env_vars.change("PY_PYTHON", Some("3.8"));
+    match Action::from_main(&["/path/to/py", "--launcher-version"].map(String::from)) {
+        Ok(Action::LauncherVersion(message)) => {
+            let printable_config_path = config_path.display();
+            assert!(message.contains(&format!("Configuration file: {printable_config_path}\n")));
+            assert!(
+                message.contains("Default interpreter: none (No executable found for Python 3.8")
+            );
+        }
+        result => panic!("Unexpected result: {result:?}"),
+    }
+
+    fs::write(&config_path, "[launch]\nsupervise = maybe\n").unwrap();
+    match Action::from_main(&["/path/to/py", "--launcher-version"].map(String::from)) {
+        Ok(Action::LauncherVersion(message)) => {
+            assert!(message.contains("Configuration file: ignored (Invalid configuration in "));
+            assert!(
+                message.contains("Default interpreter: none (No executable found for Python 3.8")
+            );
+        }
+        result => panic!("Unexpected result: {result:?}"),
+    }
+    assert!(matches!(
+        Action::from_main(&["/path/to/py", "--help"].map(String::from)),
+        Ok(Action::Help(_, _))
+    ));
+    assert!(matches!(
+        Action::from_main(&["/path/to/py", "--list"].map(String::from)),
+        Err(Error::InvalidConfig(_, _))
+    ));
+
+    assert_eq!(
+        Action::from_main(&["/path/to/py", "--launcher-version", "-3"].map(String::from)),
+        Err(Error::IllegalArgument(
+            PathBuf::from("/path/to/py"),
+            "--launcher-version".to_string()
+        ))
+    );
+}
+
+#[test]
+#[serial]
+fn from_main_doctor() {
+    let working_dir = common::CurrentDir::new();
+    let mut env_state = common::EnvState::new();
+    env_state
+        .env_vars
+        .change("PY_LAUNCHER_CONFIG", Some("this does not exist"));
+    env_state.env_vars.change("PY_PYTHON", Some("3.6"));
+    env_state.env_vars.change("PY_PYTHON3", Some("3.7"));
+    let script = working_dir.dir.path().join("script.py");
+    fs::write(&script, "#! /usr/bin/env python3.8\n").unwrap();
+
+    match Action::from_main(&[
+        "/path/to/py".to_string(),
+        "--doctor".to_string(),
+        script.to_str().unwrap().to_string(),
+    ]) {
+        Ok(Action::Doctor(report)) => {
+            let output = report.to_string();
+            assert_eq!(report.status(), python_launcher::doctor::Status::Fail);
+            assert!(output.contains("[FAIL] configuration: "));
+            let printable_python36 = env_state.python36.display();
+            assert!(output.contains(&format!(
+                "[WARN] interpreters: {printable_python36} shadows "
+            )));
+            assert!(output.contains("[WARN] PY_PYTHON*: PY_PYTHON makes `py` use Python 3.6 while PY_PYTHON3 makes `py -3` use Python 3.7\n"));
+            assert!(output.contains("[FAIL] shebang: "));
+        }
+        result => panic!("Unexpected result: {result:?}"),
+    }
+
+    // An activated virtual environment takes precedence over a found one.
+    let found_venv = working_dir.dir.path().join(".venv");
+    let activated_venv = tempfile::tempdir().unwrap();
+    for venv_dir in [found_venv.as_path(), activated_venv.path()] {
+        let bin_dir = venv_dir.join("bin");
+        fs::create_dir_all(&bin_dir).unwrap();
+        let python = bin_dir.join("python");
+        fs::write(&python, "").unwrap();
+        fs::set_permissions(&python, fs::Permissions::from_mode(0o755)).unwrap();
+        common::touch_file(venv_dir.join("pyvenv.cfg"));
+    }
+    env_state
+        .env_vars
+        .change("VIRTUAL_ENV", Some(activated_venv.path().to_str().unwrap()));
+    match Action::from_main(&["/path/to/py", "--doctor"].map(String::from)) {
+        Ok(Action::Doctor(report)) => {
+            let output = report.to_string();
+            let printable_activated = activated_venv.path().display();
+            assert!(output.contains(&format!(
+                "[PASS] virtual environment: using the activated virtual environment in {printable_activated}\n"
+            )));
+            assert!(output
+                .contains(" is not used as the activated virtual environment takes precedence\n"));
+            assert!(!output.contains("using the found virtual environment"));
+        }
+        result => panic!("Unexpected result: {result:?}"),
+    }
+
+    assert_eq!(
+        Action::from_main(&["/path/to/py", "--doctor", "a.py", "b.py"].map(String::from)),
+        Err(Error::UnrecognizedArgument(
+            PathBuf::from("/path/to/py"),
+            "b.py".to_string()
+        ))
+    );
+}
+
+#[test]
+#[serial]
+fn from_main_completions() {
+    let env_state = common::EnvState::new();
+
+    match Action::from_main(&["/path/to/py", "--completions", "fish"].map(String::from)) {
+        Ok(Action::Completions(script)) => {
+            let printable_python37 = env_state.python37.display();
+            assert!(script.contains(&format!(
+                "complete -c py --old-option 3.7 -d 'Launch {printable_python37}'\n"
+            )));
+            assert!(script.contains(&format!(
+                "complete -c py --old-option 3 -d 'Launch {printable_python37}'\n"
+            )));
+            assert!(script.contains("complete -c py --old-option 2.7 "));
+        }
+        result => panic!("Unexpected result: {result:?}"),
+    }
+
+    for (args, unrecognized) in [
+        (vec!["--completions"], "--completions"),
+        (vec!["--completions", "csh"], "csh"),
+        (vec!["--completions", "bash", "zsh"], "zsh"),
+    ] {
+        let mut argv = vec!["/path/to/py".to_string()];
+        argv.extend(args.into_iter().map(String::from));
+        assert_eq!(
+            Action::from_main(&argv),
+            Err(Error::UnrecognizedArgument(
+                PathBuf::from("/path/to/py"),
+                unrecognized.to_string()
+            ))
+        );
+    }
+}
+
+#[test]
+#[serial]
+fn from_main_prompt_info() {
+    let working_dir = common::CurrentDir::new();
+    let mut env_state = common::EnvState::new();
+
+    assert_eq!(
+        Action::from_main(&["/path/to/py", "--prompt-info"].map(String::from)),
+        Ok(Action::PromptInfo("3.7\n".to_string()))
+    );
+
+    // The interpreter is the same as the one which would be launched.
+    let probed = working_dir.dir.path().join("probed");
+    let python314 = working_dir.dir.path().join("python3.14");
+    fs::write(
+        &python314,
+        format!("#!/bin/sh\n: > '{}'\necho beta\n", probed.display()),
+    )
+    .unwrap();
+    fs::set_permissions(&python314, fs::Permissions::from_mode(0o755)).unwrap();
+    env_state.env_vars.change(
+        "PY_LAUNCHER_PATH",
+        Some(working_dir.dir.path().to_str().unwrap()),
+    );
+    env_state
+        .env_vars
+        .change("PY_LAUNCHER_PROBE_PRERELEASES", Some("true"));
+    assert_eq!(
+        Action::from_main(&["/path/to/py", "--prompt-info"].map(String::from)),
+        Ok(Action::PromptInfo("3.7\n".to_string()))
+    );
+    assert!(probed.exists());
+    match Action::from_main(&["/path/to/py"].map(String::from)) {
+        Ok(Action::Execute { executable, .. }) => assert_eq!(executable, env_state.python37),
+        result => panic!("Unexpected result: {result:?}"),
+    }
+    fs::remove_file(&python314).unwrap();
+
+    let venv_dir = working_dir.dir.path().join(".venv");
+    let bin_dir = venv_dir.join("bin");
+    fs::create_dir_all(&bin_dir).unwrap();
+    common::touch_file(bin_dir.join("python"));
+    fs::write(venv_dir.join("pyvenv.cfg"), "version = 3.6.1\n").unwrap();
+    assert_eq!(
+        Action::from_main(&["/path/to/py", "--prompt-info"].map(String::from)),
+        Ok(Action::PromptInfo("3.6 (.venv)\n".to_string()))
+    );
+
+    fs::write(
+        venv_dir.join("pyvenv.cfg"),
+        "version = 3.6.1\nprompt = 'demo'\n",
+    )
+    .unwrap();
+    assert_eq!(
+        Action::from_main(
+            &[
+                "/path/to/py",
+                "--prompt-info",
+                "🐍 {major}.{minor}[@{venv}]"
+            ]
+            .map(String::from)
+        ),
+        Ok(Action::PromptInfo("🐍 3.6@demo\n".to_string()))
+    );
+
+    assert_eq!(
+        Action::from_main(&["/path/to/py", "--prompt-info", "{nope}"].map(String::from)),
+        Err(Error::InvalidTemplate(
+            "{nope}".to_string(),
+            "unknown placeholder `{nope}`".to_string()
+        ))
+    );
+    assert_eq!(
+        Action::from_main(&["/path/to/py", "--prompt-info", "{version}", "-3"].map(String::from)),
+        Err(Error::UnrecognizedArgument(
+            PathBuf::from("/path/to/py"),
+            "-3".to_string()
+        ))
+    );
+}
+
+#[test]
+#[serial]
+fn from_main_prompt_info_budget() {
+    // `--prompt-info` runs on every shell prompt, so it must stay well under
+    // ~10ms (see benches/prompt_info.rs). The budget here is far more generous
+    // so that slow machines and debug builds pass while something like running
+    // an interpreter still fails.
+    let _working_dir = common::CurrentDir::new();
+    let _env_state = common::EnvState::new();
+    let argv = ["/path/to/py", "--prompt-info"].map(String::from);
+    let runs = 10;
+
+    let start = Instant::now();
+    for _ in 0..runs {
+        Action::from_main(&argv).unwrap();
+    }
+    let average = start.elapsed() / runs;
+
+    assert!(
+        average < Duration::from_millis(50),
+        "`--prompt-info` took {average:?} on average"
+    );
+}
+
+#[test]
+#[serial]
+fn from_main_shell_init() {
+    let working_dir = common::CurrentDir::new();
+    let mut env_state = common::EnvState::new();
+
+    let Ok(Action::ShellInit(script)) =
+        Action::from_main(&["/path/to/py", "--shell-init", "zsh"].map(String::from))
+    else {
+        panic!("expected the zsh hook script");
+    };
+    assert!(script.contains("add-zsh-hook chpwd _py_launcher_hook"));
+
+    assert_eq!(
+        Action::from_main(&["/path/to/py", "--shell-init", "bash", "--hook"].map(String::from)),
+        Ok(Action::ShellInit(String::new()))
+    );
+
+    let venv_dir = working_dir.dir.path().join(".venv");
+    let bin_dir = venv_dir.join("bin");
+    fs::create_dir_all(&bin_dir).unwrap();
+    common::touch_file(bin_dir.join("python"));
+    fs::write(venv_dir.join("pyvenv.cfg"), "version = 3.6.1\n").unwrap();
+    let Ok(Action::ShellInit(commands)) =
+        Action::from_main(&["/path/to/py", "--shell-init", "bash", "--hook"].map(String::from))
+    else {
+        panic!("expected commands to activate the virtual environment");
+    };
+    assert!(commands.contains(&format!("export VIRTUAL_ENV='{}';\n", venv_dir.display())));
+    assert!(commands.contains(&format!("export PATH='{}:", bin_dir.display())));
+
+    // A manually activated virtual environment is left alone.
+    env_state
+        .env_vars
+        .change("VIRTUAL_ENV", Some("/manual/venv"));
+    assert_eq!(
+        Action::from_main(&["/path/to/py", "--shell-init", "fish", "--hook"].map(String::from)),
+        Ok(Action::ShellInit(String::new()))
+    );
+
+    assert_eq!(
+        Action::from_main(&["/path/to/py", "--shell-init"].map(String::from)),
+        Err(Error::UnrecognizedArgument(
+            PathBuf::from("/path/to/py"),
+            "--shell-init".to_string()
+        ))
+    );
+    assert_eq!(
+        Action::from_main(&["/path/to/py", "--shell-init", "nushell"].map(String::from)),
+        Err(Error::UnrecognizedArgument(
+            PathBuf::from("/path/to/py"),
+            "nushell".to_string()
+        ))
+    );
+    assert_eq!(
+        Action::from_main(&["/path/to/py", "--shell-init", "bash", "extra"].map(String::from)),
+        Err(Error::UnrecognizedArgument(
+            PathBuf::from("/path/to/py"),
+            "extra".to_string()
+        ))
     );
 }
@@ -6,8 +6,33 @@ use std::path::PathBuf;
 
 use tempfile::TempDir;
 
+/// Environment variables which influence the search for an interpreter.
+pub const SEARCH_ENV_VARS: [&str; 20] = [
+    "VIRTUAL_ENV",
+    "PY_PYTHON",
+    "PY_PYTHON3",
+    "PY_PYTHON2",
+    "PY_LAUNCHER_CONFIG",
+    "PY_LAUNCHER_VENV_DIRS",
+    "PY_LAUNCHER_VENV_SEARCH",
+    "PY_LAUNCHER_VENV_CEILING_DIRS",
+    "PY_LAUNCHER_VENV_ALLOW_UNSAFE",
+    "PY_LAUNCHER_SHELL_VENV",
+    "PY_LAUNCHER_PATH",
+    "PY_LAUNCHER_PATH_POSITION",
+    "PY_LAUNCHER_EXCLUDE",
+    "PY_LAUNCHER_PRERELEASES",
+    "PY_LAUNCHER_PROBE_PRERELEASES",
+    "PY_LAUNCHER_STABLE",
+    "PY_LAUNCHER_PREFER_SYSTEM",
+    "PY_LAUNCHER_SUPERVISE",
+    "PY_LAUNCHER_POST_EXIT",
+    "PY_LAUNCHER_ERROR_FORMAT",
+];
+
 pub struct EnvVarState {
     changed: HashMap<OsString, Option<OsString>>,
+    _config_home: TempDir,
 }
 
 impl Drop for EnvVarState {
@@ -20,17 +45,23 @@ impl Drop for EnvVarState {
 }
 
 impl EnvVarState {
+    /// Points `XDG_CONFIG_HOME` at an empty temporary directory so that the
+    /// developer's own configuration file is never used.
     pub fn new() -> Self {
-        Self {
+        let mut state = Self {
             changed: HashMap::new(),
-        }
+            _config_home: TempDir::new().unwrap(),
+        };
+        let config_home = state._config_home.path().to_str().unwrap().to_string();
+        state.change("XDG_CONFIG_HOME", Some(&config_home));
+        state
     }
 
     #[allow(dead_code)]
     pub fn empty() -> Self {
         let mut state = Self::new();
         state.change("PATH", None);
-        for env_var in ["VIRTUAL_ENV", "PY_PYTHON", "PY_PYTHON3", "PY_PYTHON2"].iter() {
+        for env_var in SEARCH_ENV_VARS.iter() {
             state.change(env_var, None);
         }
 
@@ -100,6 +131,8 @@ impl EnvState {
     /// - `PY_PYTHON` is unset
     /// - `PY_PYTHON3` is unset
     /// - `PY_PYTHON2` is unset
+    /// - `PY_LAUNCHER_*` configuration environment variables are unset
+    /// - `XDG_CONFIG_HOME` is set to an empty temporary directory
     #[allow(dead_code)]
     pub fn new() -> Self {
         let dir1 = TempDir::new().unwrap();
@@ -113,7 +146,7 @@ impl EnvState {
         let new_path = env::join_paths([dir1.path(), dir2.path()].iter()).unwrap();
         let mut env_changes = EnvVarState::new();
         env_changes.change("PATH", Some(new_path.to_str().unwrap()));
-        for env_var in ["VIRTUAL_ENV", "PY_PYTHON", "PY_PYTHON3", "PY_PYTHON2"].iter() {
+        for env_var in SEARCH_ENV_VARS.iter() {
             env_changes.change(env_var, None);
         }
 
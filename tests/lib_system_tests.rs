@@ -2,7 +2,7 @@ mod common;
 
 use serial_test::serial;
 
-use python_launcher::{ExactVersion, RequestedVersion};
+use python_launcher::{config::Config, ExactVersion, RequestedVersion};
 
 use common::EnvState;
 
@@ -57,3 +57,20 @@ fn find_executable() {
         Some(env_state.python36)
     );
 }
+
+#[test]
+#[serial]
+fn find_executable_with_config() {
+    let env_state = EnvState::new();
+    let config = Config {
+        search_exclude: vec!["3.7".to_string()],
+        ..Config::default()
+    };
+
+    assert_eq!(
+        python_launcher::find_executable_with_config(RequestedVersion::Any, &config),
+        Some(env_state.python36)
+    );
+    assert!(!python_launcher::all_executables_with_config(&config)
+        .contains_key(&ExactVersion::new(3, 7)));
+}
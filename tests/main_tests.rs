@@ -2,25 +2,45 @@
 
 mod common;
 
-use std::path::PathBuf;
+use std::{
+    ffi::OsStr,
+    os::unix::{ffi::OsStrExt, fs::PermissionsExt, process::ExitStatusExt},
+    path::{Path, PathBuf},
+};
 
 use common::CurrentDir;
 
-use python_launcher::{ExactVersion, RequestedVersion};
+use python_launcher::{config::Config, ExactVersion, RequestedVersion};
 
 use assert_cmd::Command;
 use predicates::str;
 use test_case::test_case;
 
+/// The `py` binary, isolated from the developer's configuration.
 fn py_executable() -> Command {
-    Command::cargo_bin("py").expect("binary 'py' not found")
+    let mut command = Command::cargo_bin("py").expect("binary 'py' not found");
+    command.env(
+        "XDG_CONFIG_HOME",
+        PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("no-config"),
+    );
+    for env_var in common::SEARCH_ENV_VARS {
+        if env_var.starts_with("PY_LAUNCHER_") {
+            command.env_remove(env_var);
+        }
+    }
+    command
+}
+
+/// The interpreter `py` runs by default, using the same configuration.
+fn default_executable() -> PathBuf {
+    python_launcher::find_executable_with_config(RequestedVersion::Any, &Config::default())
+        .expect("no Python executable found")
 }
 
 #[test_case("-h"; "short")]
 #[test_case("--help"; "long")]
 fn help_flags(help_flag: &str) {
-    let python = python_launcher::find_executable(RequestedVersion::Any)
-        .expect("no Python executable found");
+    let python = default_executable();
     let result = py_executable().arg(help_flag).assert();
 
     result
@@ -31,7 +51,7 @@ fn help_flags(help_flag: &str) {
 
 #[test]
 fn list_output() {
-    let pythons = python_launcher::all_executables();
+    let pythons = python_launcher::all_executables_with_config(&Config::default());
     let mut result = py_executable().arg("--list").assert();
 
     result = result.success();
@@ -45,8 +65,7 @@ fn list_output() {
 
 #[test]
 fn any_version() {
-    let python = python_launcher::find_executable(RequestedVersion::Any)
-        .expect("no Python executable found");
+    let python = default_executable();
     let version = ExactVersion::from_path(&python).unwrap();
     let result = py_executable()
         .args(["-c", "import sys; print(sys.version)"])
@@ -60,8 +79,7 @@ fn any_version() {
 
 #[test]
 fn major_version() {
-    let python = python_launcher::find_executable(RequestedVersion::Any)
-        .expect("no Python executable found");
+    let python = default_executable();
     let version = ExactVersion::from_path(&python).unwrap();
     let version_flag = format!("-{}", version.major);
     let result = py_executable()
@@ -80,8 +98,7 @@ fn major_version() {
 
 #[test]
 fn exact_version() {
-    let python = python_launcher::find_executable(RequestedVersion::Any)
-        .expect("no Python executable found");
+    let python = default_executable();
     let version = ExactVersion::from_path(&python).unwrap();
     let version_flag = format!("-{version}");
     let result = py_executable()
@@ -120,13 +137,27 @@ fn version_failure(version: &str) {
     result.failure().stdout(str::is_empty());
 }
 
+#[test]
+fn no_executable_for_version() {
+    let result = py_executable().arg("-0.1").assert();
+
+    result
+        .failure()
+        .code(python_launcher::EXIT_NO_EXECUTABLE)
+        .stdout(str::is_empty())
+        .stderr(str::contains("No executable found for Python 0.1"));
+}
+
 #[test]
 fn nonexistent_activated_virtual_env_dir() {
     let result = py_executable()
         .env("VIRTUAL_ENV", "this does not exist")
         .assert();
 
-    result.failure().stdout(str::is_empty());
+    result
+        .failure()
+        .code(python_launcher::EXIT_BROKEN_VENV)
+        .stdout(str::is_empty());
 }
 
 #[test]
@@ -136,7 +167,38 @@ fn empty_activated_virtual_env() {
         .env("VIRTUAL_ENV", cwd.dir.path().as_os_str())
         .assert();
 
-    result.failure();
+    result.failure().code(python_launcher::EXIT_BROKEN_VENV);
+}
+
+#[test]
+fn invalid_config() {
+    let result = py_executable()
+        .env("PY_LAUNCHER_CONFIG", "this does not exist")
+        .assert();
+
+    result
+        .failure()
+        .code(python_launcher::EXIT_INVALID_CONFIG)
+        .stdout(str::is_empty());
+}
+
+#[test]
+fn exec_failure() {
+    // An activated virtual environment whose executable lacks the execute
+    // permission.
+    let dir = tempfile::tempdir().unwrap();
+    let bin_dir = dir.path().join("bin");
+    std::fs::create_dir(&bin_dir).unwrap();
+    common::touch_file(bin_dir.join("python"));
+    let result = py_executable()
+        .env("VIRTUAL_ENV", dir.path().as_os_str())
+        .current_dir(dir.path())
+        .assert();
+
+    result
+        .failure()
+        .code(python_launcher::EXIT_EXEC_FAILED)
+        .stdout(str::is_empty());
 }
 
 #[test]
@@ -149,3 +211,192 @@ fn no_executable() {
 
     result.failure();
 }
+
+#[test]
+fn venv_exists() {
+    let dir = tempfile::tempdir().unwrap();
+    let venv_dir = dir.path().join(".venv");
+    std::fs::create_dir(&venv_dir).unwrap();
+    common::touch_file(venv_dir.join("pyvenv.cfg"));
+    let result = py_executable()
+        .args(["--venv", ".venv"])
+        .current_dir(dir.path())
+        .assert();
+
+    result
+        .failure()
+        .code(exitcode::CANTCREAT)
+        .stdout(str::is_empty())
+        .stderr(str::contains("--force"));
+}
+
+#[test]
+fn venv_force_not_a_venv() {
+    let dir = tempfile::tempdir().unwrap();
+    let src_dir = dir.path().join("src");
+    std::fs::create_dir(&src_dir).unwrap();
+    std::fs::write(src_dir.join("main.py"), "print('Hello')\n").unwrap();
+    let result = py_executable()
+        .args(["--venv", "--force", "src"])
+        .current_dir(dir.path())
+        .assert();
+
+    result
+        .failure()
+        .code(exitcode::CANTCREAT)
+        .stdout(str::is_empty())
+        .stderr(str::contains("not a virtual environment"));
+    assert_eq!(
+        std::fs::read_to_string(src_dir.join("main.py")).unwrap(),
+        "print('Hello')\n"
+    );
+    assert_eq!(std::fs::read_dir(&src_dir).unwrap().count(), 1);
+}
+
+#[test]
+fn json_error_format_flag() {
+    let result = py_executable()
+        .args(["--error-format=json", "-0.1"])
+        .assert();
+
+    result
+        .failure()
+        .code(python_launcher::EXIT_NO_EXECUTABLE)
+        .stdout(str::is_empty())
+        .stderr(str::starts_with(
+            r#"{"kind":"no_executable_found","message":"No executable found for Python 0.1"#,
+        ))
+        .stderr(str::contains(r#""exit_code":200,"#))
+        .stderr(str::contains(r#""requested_version":"0.1""#));
+}
+
+#[test]
+fn json_error_format_env_var() {
+    let result = py_executable()
+        .args(["--list", "--help"])
+        .env("PY_LAUNCHER_ERROR_FORMAT", "json")
+        .assert();
+
+    result
+        .failure()
+        .stderr(str::starts_with(r#"{"kind":"illegal_argument","#));
+}
+
+#[test]
+fn unknown_error_format() {
+    let result = py_executable()
+        .args(["--error-format=xml", "-c", "pass"])
+        .assert();
+
+    result
+        .failure()
+        .code(exitcode::USAGE)
+        .stderr(str::contains("--error-format=xml"));
+}
+
+#[test]
+fn launcher_version() {
+    let result = py_executable().arg("--launcher-version").assert();
+
+    result
+        .success()
+        .stdout(str::starts_with(format!(
+            "Python Launcher for Unix {}\n",
+            env!("CARGO_PKG_VERSION")
+        )))
+        .stdout(str::contains("Default interpreter: "))
+        .stderr(str::is_empty());
+}
+
+#[test]
+fn doctor() {
+    let dir = tempfile::tempdir().unwrap();
+    let result = py_executable()
+        .arg("--doctor")
+        .env("PATH", dir.path())
+        .current_dir(dir.path())
+        .assert();
+
+    result
+        .code(1)
+        .stdout(str::contains("[FAIL] interpreters: "))
+        .stdout(str::ends_with(" failed\n"))
+        .stderr(str::is_empty());
+}
+
+/// Creates an "interpreter" for the virtual environment in `dir` which runs
+/// `script` via `sh`.
+fn fake_interpreter(dir: &Path, script: &str) {
+    let bin_dir = dir.join("bin");
+    std::fs::create_dir(&bin_dir).unwrap();
+    let python = bin_dir.join("python");
+    std::fs::write(&python, format!("#!/bin/sh\n{script}\n")).unwrap();
+    std::fs::set_permissions(&python, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[test]
+fn supervise_exit_status() {
+    let result = py_executable()
+        .env("PY_LAUNCHER_SUPERVISE", "true")
+        .args(["-c", "import sys; print('supervised'); sys.exit(3)"])
+        .assert();
+
+    result.code(3).stdout("supervised\n");
+}
+
+#[test]
+fn supervise_signal() {
+    // An activated virtual environment whose "interpreter" kills itself.
+    let dir = tempfile::tempdir().unwrap();
+    fake_interpreter(dir.path(), "kill -TERM $$");
+    let output = py_executable()
+        .env("PY_LAUNCHER_SUPERVISE", "true")
+        .env("VIRTUAL_ENV", dir.path().as_os_str())
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.signal(), Some(15));
+}
+
+#[test]
+fn post_exit() {
+    let dir = tempfile::tempdir().unwrap();
+    let status_file = dir.path().join("status");
+    let result = py_executable()
+        .env(
+            "PY_LAUNCHER_POST_EXIT",
+            format!(
+                "echo \"$PY_LAUNCHER_EXIT_STATUS\" > '{}'",
+                status_file.display()
+            ),
+        )
+        .args(["-c", "raise SystemExit(5)"])
+        .current_dir(dir.path())
+        .assert();
+
+    result.code(5);
+    assert_eq!(std::fs::read_to_string(status_file).unwrap(), "5\n");
+}
+
+#[test_case(false ; "executed")]
+#[test_case(true ; "supervised")]
+fn non_utf8_args(supervise: bool) {
+    let dir = tempfile::tempdir().unwrap();
+    fake_interpreter(dir.path(), "printf '%s\\n' \"$@\"");
+    let mut command = py_executable();
+    if supervise {
+        command.env("PY_LAUNCHER_SUPERVISE", "true");
+    }
+    let result = command
+        .env("VIRTUAL_ENV", dir.path().as_os_str())
+        .current_dir(dir.path())
+        .arg(OsStr::from_bytes(b"caf\xe9.py"))
+        .arg("--")
+        .assert();
+
+    result
+        .success()
+        .stdout(b"caf\xe9.py\n--\n".as_slice())
+        .stderr(str::is_empty());
+}